@@ -0,0 +1,225 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{convert, Failure, Options};
+
+// a whole directory tree converted with the same options
+pub struct BatchOptions {
+    // searched recursively for .sup files
+    pub root: PathBuf,
+    // outputs go here under the same relative paths, next to their inputs when None
+    pub out_dir: Option<PathBuf>,
+    // convert files whose output already exists
+    pub force: bool,
+    // the options every file is converted with, input and output are set per file
+    pub options: Options,
+}
+
+enum Outcome {
+    // with the number of cues written, None for a retimed .sup
+    Converted(Option<usize>),
+    // the output was already there
+    Skipped,
+    Failed(Failure),
+}
+
+// every .sup under the root converted one after the other, the OCR of each runs on the
+// converter's one thread pool
+pub fn run(batch: &BatchOptions) -> Result<(), Failure> {
+    let skip = match &batch.out_dir {
+        Some(dir) => fs::canonicalize(dir).ok(),
+        None => None,
+    };
+    let mut inputs = Vec::new();
+    find_inputs(&batch.root, skip.as_deref(), &mut inputs)?;
+
+    let mut results = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let output = output_path(batch, &input);
+        let outcome = if !batch.force && output.exists() {
+            Outcome::Skipped
+        } else {
+            let options = Options {
+                input: input.clone(),
+                output,
+                ..batch.options.clone()
+            };
+            match convert_to(&options) {
+                Ok(cues) => Outcome::Converted(cues),
+                Err(failure) => Outcome::Failed(failure),
+            }
+        };
+
+        if batch.options.verbosity > 0 {
+            eprintln!("{} {}", input.display(), outcome.label());
+        }
+        results.push((input, outcome));
+    }
+
+    if batch.options.verbosity >= 0 {
+        write_summary(&mut io::stdout().lock(), &batch.root, &results)?;
+    }
+
+    match results
+        .iter()
+        .filter(|(_, outcome)| matches!(outcome, Outcome::Failed(_)))
+        .count()
+    {
+        0 => Ok(()),
+        failed => Err(Failure::Batch(failed)),
+    }
+}
+
+fn convert_to(options: &Options) -> Result<Option<usize>, Failure> {
+    if let Some(parent) = options.output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    convert(options)
+}
+
+// .sup files under dir in name order, so a batch runs the same way every time. Symlinked
+// directories aren't followed, and skip (the output directory) is left out so the outputs of a
+// retiming batch aren't picked up as inputs on the next run
+fn find_inputs(dir: &Path, skip: Option<&Path>, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if skip.is_none() || fs::canonicalize(&path).ok().as_deref() != skip {
+                find_inputs(&path, skip, found)?;
+            }
+        } else if is_input(&path) && path.is_file() {
+            found.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn is_input(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.to_ascii_lowercase(),
+        None => return false,
+    };
+
+    // an earlier retiming batch wrote these next to their inputs
+    name.ends_with(".sup") && !name.ends_with(".retimed.sup")
+}
+
+fn output_path(batch: &BatchOptions, input: &Path) -> PathBuf {
+    let extension = batch.options.format.extension();
+    match &batch.out_dir {
+        Some(dir) => dir
+            .join(input.strip_prefix(&batch.root).unwrap_or(input))
+            .with_extension(extension),
+        None => input.with_extension(extension),
+    }
+}
+
+impl Outcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Converted(_) => "converted",
+            Outcome::Skipped => "skipped",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+// one row per file with its result and cue count, failures with their reason, then the totals
+fn write_summary(
+    out: &mut dyn Write,
+    root: &Path,
+    results: &[(PathBuf, Outcome)],
+) -> io::Result<()> {
+    let names: Vec<String> = results
+        .iter()
+        .map(|(input, _)| {
+            input
+                .strip_prefix(root)
+                .unwrap_or(input)
+                .display()
+                .to_string()
+        })
+        .collect();
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+
+    writeln!(out, "{:<width$}  {:<9}  {:>6}", "file", "result", "cues")?;
+    let (mut converted, mut skipped, mut failed, mut total_cues) = (0, 0, 0, 0);
+    for (name, (_, outcome)) in names.iter().zip(results) {
+        let cues = match outcome {
+            Outcome::Converted(Some(cues)) => cues.to_string(),
+            _ => "-".to_string(),
+        };
+        write!(out, "{:<width$}  {:<9}  {:>6}", name, outcome.label(), cues)?;
+
+        match outcome {
+            Outcome::Converted(cues) => {
+                converted += 1;
+                total_cues += cues.unwrap_or(0);
+            }
+            Outcome::Skipped => skipped += 1,
+            Outcome::Failed(failure) => {
+                failed += 1;
+                write!(out, "  {}", failure)?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    writeln!(
+        out,
+        "{} files: {} converted, {} skipped, {} failed, {} cues",
+        results.len(),
+        converted,
+        skipped,
+        failed,
+        total_cues
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cap_parser::Error;
+
+    #[test]
+    fn test_is_input() {
+        assert!(is_input(Path::new("disc/MOVIE.SUP")));
+        assert!(!is_input(Path::new("disc/movie.retimed.sup")));
+        assert!(!is_input(Path::new("disc/movie.srt")));
+    }
+
+    #[test]
+    fn test_summary() {
+        let root = Path::new("library");
+        let results = [
+            (root.join("a/one.sup"), Outcome::Converted(Some(812))),
+            (root.join("two.sup"), Outcome::Skipped),
+            (
+                root.join("three.sup"),
+                Outcome::Failed(Failure::BadInput(Error::Parse { offset: 0 })),
+            ),
+        ];
+
+        let mut out = Vec::new();
+        write_summary(&mut out, root, &results).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "file       result       cues\n\
+             a/one.sup  converted     812\n\
+             two.sup    skipped         -\n\
+             three.sup  failed          -  the input is not a PGS stream that can be read, no PGS packet at byte 0\n\
+             3 files: 1 converted, 1 skipped, 1 failed, 812 cues\n"
+        );
+    }
+}
@@ -0,0 +1,731 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use structopt::StructOpt;
+
+use cap_parser::parser::renderer::FrameSnap;
+use cap_parser::parser::timestamp::CLOCK_RATE;
+use cap_parser::parser::types::{FrameRate, Timestamp};
+use cap_parser::report::ReviewOptions;
+use cap_parser::retime::Retime;
+use cap_parser::subtitle::ttml::TtmlTiming;
+use cap_parser::subtitle::vobsub::TvSystem;
+use cap_parser::subtitle::LineEnding;
+use cap_parser::{Converter, Recognition};
+
+use crate::batch::BatchOptions;
+use crate::dump::DumpOptions;
+use crate::inspect::{InspectOptions, SEGMENT_NAMES};
+use crate::repair::RepairOptions;
+use crate::splice::{CutOptions, MergeOptions};
+use crate::validate::ValidateOptions;
+use crate::Options;
+
+// stands for stdin as the input and stdout as the output
+pub const STDIO: &str = "-";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Srt,
+    Vtt,
+    Ass,
+    Ttml,
+    // IMSC1 image profile, the bitmaps as PNGs next to the document
+    TtmlImage,
+    // a .idx and .sub pair
+    VobSub,
+    // the input retimed
+    Sup,
+}
+
+impl Format {
+    fn from_extension(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "srt" => Some(Format::Srt),
+            "vtt" => Some(Format::Vtt),
+            "ass" | "ssa" => Some(Format::Ass),
+            "ttml" | "xml" => Some(Format::Ttml),
+            "idx" | "sub" => Some(Format::VobSub),
+            "sup" => Some(Format::Sup),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Srt => "srt",
+            Format::Vtt => "vtt",
+            Format::Ass => "ass",
+            Format::Ttml | Format::TtmlImage => "ttml",
+            Format::VobSub => "idx",
+            // never the input's own name
+            Format::Sup => "retimed.sup",
+        }
+    }
+
+    // the outputs that need a cargo feature, when this build is without it
+    fn missing_feature(self) -> Option<&'static str> {
+        match self {
+            Format::Srt | Format::Vtt | Format::Ass | Format::Ttml if !cfg!(feature = "ocr") => {
+                Some("ocr")
+            }
+            Format::TtmlImage if !cfg!(feature = "image") => Some("image"),
+            _ => None,
+        }
+    }
+
+    // written as several files named after the output path, so they can't go to stdout
+    fn writes_files(self) -> bool {
+        matches!(self, Format::TtmlImage | Format::VobSub)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "srt" => Ok(Format::Srt),
+            "vtt" | "webvtt" => Ok(Format::Vtt),
+            "ass" | "ssa" => Ok(Format::Ass),
+            "ttml" | "imsc" => Ok(Format::Ttml),
+            "ttml-image" | "imsc-image" => Ok(Format::TtmlImage),
+            "vobsub" | "idx" => Ok(Format::VobSub),
+            "sup" | "pgs" => Ok(Format::Sup),
+            _ => Err(format!("unknown format {}", s)),
+        }
+    }
+}
+
+// what the command line asks for
+pub enum Task {
+    Convert(Options),
+    Batch(BatchOptions),
+    Dump(DumpOptions),
+    Inspect(InspectOptions),
+    Validate(ValidateOptions),
+    Repair(RepairOptions),
+    Cut(CutOptions),
+    Merge(MergeOptions),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "cap-parser",
+    about = "Converts Blu-ray PGS (.sup) subtitles to text, image and DVD subtitle formats"
+)]
+struct Cli {
+    #[structopt(parse(from_os_str), help = "The .sup to read, - reads stdin")]
+    input: Option<PathBuf>,
+
+    #[structopt(
+        short,
+        long,
+        parse(from_os_str),
+        help = "Where to write, - writes stdout. Defaults to the input with the format's extension"
+    )]
+    output: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    convert: ConvertArgs,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+// parsed once, the size of the batch options doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, StructOpt)]
+enum Command {
+    #[structopt(about = "Converts every .sup in a directory tree")]
+    Batch {
+        #[structopt(parse(from_os_str), help = "The directory to search for .sup files")]
+        dir: PathBuf,
+
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help = "Write into this directory, mirroring the input tree, rather than next to each input"
+        )]
+        out_dir: Option<PathBuf>,
+
+        #[structopt(long, help = "Convert files again even if their output exists")]
+        force: bool,
+
+        #[structopt(flatten)]
+        convert: ConvertArgs,
+    },
+
+    #[structopt(about = "Prints every packet of a .sup as it was parsed")]
+    Dump {
+        #[structopt(parse(from_os_str), help = "The .sup to read, - reads stdin")]
+        input: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            parse(from_os_str),
+            default_value = "-",
+            help = "Where to write, - writes stdout"
+        )]
+        output: PathBuf,
+
+        #[structopt(long, help = "Write JSON, object data as base64 of its RLE bytes")]
+        json: bool,
+
+        #[structopt(
+            long,
+            requires = "json",
+            help = "Write the size of each object's data rather than the data"
+        )]
+        summarize_rle: bool,
+    },
+
+    #[structopt(about = "Prints every packet of a .sup decoded, grouped by display set and epoch")]
+    Inspect {
+        #[structopt(parse(from_os_str), help = "The .sup to read, - reads stdin")]
+        input: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            parse(from_os_str),
+            default_value = "-",
+            help = "Where to write, - writes stdout"
+        )]
+        output: PathBuf,
+
+        #[structopt(
+            long,
+            help = "Leave out display sets before this time, as [[hh:]mm:]ss[.mmm]"
+        )]
+        from: Option<Timestamp>,
+
+        #[structopt(
+            long,
+            help = "Leave out display sets after this time, as [[hh:]mm:]ss[.mmm]"
+        )]
+        to: Option<Timestamp>,
+
+        #[structopt(
+            long,
+            use_delimiter = true,
+            possible_values = &SEGMENT_NAMES,
+            case_insensitive = true,
+            help = "Print only these segment types, comma separated"
+        )]
+        segments: Vec<String>,
+    },
+
+    #[structopt(about = "Checks a .sup against the HDMV PGS rules and lists every violation")]
+    Validate {
+        #[structopt(parse(from_os_str), help = "The .sup to read, - reads stdin")]
+        input: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            parse(from_os_str),
+            default_value = "-",
+            help = "Where to write the report, - writes stdout"
+        )]
+        output: PathBuf,
+
+        #[structopt(
+            long,
+            help = "Also check display sets can be decoded and drawn in time by a player's decoder"
+        )]
+        decoder_model: bool,
+    },
+
+    #[structopt(
+        about = "Rebuilds a damaged .sup: adds missing ENDs, drops what can't be used, renumbers compositions and recalculates DTS"
+    )]
+    Repair {
+        #[structopt(parse(from_os_str), help = "The .sup to read, - reads stdin")]
+        input: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            parse(from_os_str),
+            help = "Where to write, - writes stdout. Defaults to the input with .repaired.sup"
+        )]
+        output: Option<PathBuf>,
+
+        #[structopt(short, long, help = "Print nothing, not even errors")]
+        quiet: bool,
+    },
+
+    #[structopt(
+        about = "Writes the display sets of a .sup presented in a time range, starting a fresh epoch"
+    )]
+    Cut {
+        #[structopt(parse(from_os_str), help = "The .sup to read, - reads stdin")]
+        input: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            parse(from_os_str),
+            help = "Where to write, - writes stdout"
+        )]
+        output: PathBuf,
+
+        #[structopt(long, help = "Start of the range, as [[hh:]mm:]ss[.mmm]")]
+        from: Option<Timestamp>,
+
+        #[structopt(
+            long,
+            help = "End of the range (not included), as [[hh:]mm:]ss[.mmm]. A subtitle showing then is cleared"
+        )]
+        to: Option<Timestamp>,
+    },
+
+    #[structopt(about = "Joins .sup files into one, renumbering compositions")]
+    Merge {
+        #[structopt(
+            required = true,
+            parse(try_from_str = parse_merge_input),
+            help = "The .sup files in order, each can be moved later as file@[[hh:]mm:]ss[.mmm]"
+        )]
+        inputs: Vec<(PathBuf, Timestamp)>,
+
+        #[structopt(
+            short,
+            long,
+            parse(from_os_str),
+            help = "Where to write, - writes stdout"
+        )]
+        output: PathBuf,
+    },
+}
+
+// the conversion options, the same for a single file and a batch
+#[derive(Debug, StructOpt)]
+struct ConvertArgs {
+    #[structopt(
+        short,
+        long,
+        help = "srt, vtt, ass, ttml, ttml-image, vobsub or sup. Defaults to the output's extension, then srt"
+    )]
+    format: Option<Format>,
+
+    #[structopt(
+        short,
+        long,
+        default_value = "eng",
+        help = "Tesseract language to read the subtitles in, several can be joined by +"
+    )]
+    language: String,
+
+    #[structopt(
+        short = "j",
+        long,
+        help = "OCR threads, defaults to the number of CPUs"
+    )]
+    threads: Option<usize>,
+
+    #[structopt(
+        short,
+        long,
+        parse(from_occurrences),
+        help = "Print timing and a summary, twice to also print every cue as it's read"
+    )]
+    verbose: u8,
+
+    #[structopt(
+        short,
+        long,
+        conflicts_with = "verbose",
+        help = "Print nothing, not even errors"
+    )]
+    quiet: bool,
+
+    #[structopt(long, help = "Recognize glyphs seen before without running tesseract")]
+    glyphs: bool,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "Glyph table to load and save, implies --glyphs"
+    )]
+    glyph_table: Option<PathBuf>,
+
+    #[structopt(long, parse(from_os_str), help = "Keep OCR results here between runs")]
+    cache_dir: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "Write the OCR confidence of every cue and line here as JSON"
+    )]
+    confidence: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "List the cues with a low OCR confidence here"
+    )]
+    review: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        default_value = "70",
+        help = "Cues below this mean confidence (0 to 100) are listed for review"
+    )]
+    review_threshold: f32,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        default_value = "review",
+        help = "The images of cues listed for review are saved here"
+    )]
+    review_images: PathBuf,
+
+    #[structopt(long, help = "Join consecutive cues showing the same bitmap")]
+    merge_repeated: bool,
+
+    #[structopt(
+        long,
+        help = "Snap cue times to frame boundaries, at the stream's frame rate or the one given"
+    )]
+    snap_frames: Option<Option<FrameRate>>,
+
+    #[structopt(long, help = "srt: CRLF line endings")]
+    crlf: bool,
+
+    #[structopt(long, help = "srt: start with a UTF-8 byte order mark")]
+    bom: bool,
+
+    #[structopt(long, help = "ttml: times in ticks rather than clock time")]
+    ttml_ticks: bool,
+
+    #[structopt(long, help = "vobsub: 720x576 rather than 720x480")]
+    pal: bool,
+
+    #[structopt(
+        long,
+        allow_hyphen_values = true,
+        help = "sup: move every timestamp by this many milliseconds"
+    )]
+    shift: Option<i64>,
+
+    #[structopt(
+        long,
+        parse(try_from_str = parse_ratio),
+        help = "sup: multiply every timestamp by NUM/DEN"
+    )]
+    scale: Option<(i64, i64)>,
+
+    #[structopt(
+        long,
+        parse(try_from_str = parse_rates),
+        help = "sup: retime for a different video frame rate, FROM:TO as in 23.976:25"
+    )]
+    convert_rate: Option<(FrameRate, FrameRate)>,
+
+    #[structopt(
+        long,
+        number_of_values = 1,
+        parse(try_from_str = parse_sync_point),
+        help = "sup: OLD=NEW, given twice, stretches the stream so both OLD times land on NEW"
+    )]
+    sync: Vec<(Timestamp, Timestamp)>,
+}
+
+fn parse_ratio(s: &str) -> Result<(i64, i64), String> {
+    let bad = || format!("bad ratio {}, expected NUM/DEN", s);
+    let (num, den) = s.split_once('/').ok_or_else(bad)?;
+    let num: i64 = num.parse().map_err(|_| bad())?;
+    let den: i64 = den.parse().map_err(|_| bad())?;
+    // anything else runs the stream backwards or moves it all to 0
+    if num <= 0 || den <= 0 {
+        return Err(format!("bad ratio {}, NUM and DEN have to be above 0", s));
+    }
+    Ok((num, den))
+}
+
+fn parse_rates(s: &str) -> Result<(FrameRate, FrameRate), String> {
+    let (from, to) = s
+        .split_once(':')
+        .ok_or_else(|| format!("bad frame rates {}, expected FROM:TO", s))?;
+    Ok((from.parse()?, to.parse()?))
+}
+
+// movie.sup, or movie.sup@1:30:00 to move its timestamps 90 minutes later
+fn parse_merge_input(s: &str) -> Result<(PathBuf, Timestamp), String> {
+    match s.rsplit_once('@') {
+        Some((path, offset)) => Ok((PathBuf::from(path), offset.parse()?)),
+        None => Ok((PathBuf::from(s), Timestamp::default())),
+    }
+}
+
+fn parse_sync_point(s: &str) -> Result<(Timestamp, Timestamp), String> {
+    let (old, new) = s
+        .split_once('=')
+        .ok_or_else(|| format!("bad sync point {}, expected OLD=NEW", s))?;
+    Ok((old.parse()?, new.parse()?))
+}
+
+impl ConvertArgs {
+    fn retime(&self) -> Result<Option<Retime>, String> {
+        let mut retimes: Vec<Retime> = Vec::new();
+        if let Some(ms) = self.shift {
+            retimes.push(Retime::Shift(ms * (CLOCK_RATE / 1_000) as i64));
+        }
+        if let Some((num, den)) = self.scale {
+            retimes.push(Retime::Scale { num, den });
+        }
+        if let Some((from, to)) = self.convert_rate {
+            retimes.push(Retime::FrameRate { from, to });
+        }
+        match self.sync.as_slice() {
+            [] => {}
+            [first, second] if first.0 == second.0 => {
+                return Err("the two --sync points need different OLD times".to_string())
+            }
+            // the new times in the other order would run the stream backwards
+            [first, second] if (first.0 < second.0) != (first.1 < second.1) => {
+                return Err("--sync NEW times have to be in the same order as OLD".to_string())
+            }
+            [first, second] => retimes.push(Retime::Sync {
+                first: *first,
+                second: *second,
+            }),
+            _ => return Err("--sync takes exactly two points".to_string()),
+        }
+
+        if retimes.len() > 1 {
+            return Err(
+                "only one of --shift, --scale, --convert-rate and --sync can be used".to_string(),
+            );
+        }
+        Ok(retimes.pop())
+    }
+
+    fn into_options(self, input: PathBuf, output: Option<PathBuf>) -> Result<Options, String> {
+        let retime = self.retime()?;
+        let format = match (self.format, &output) {
+            (Some(format), _) => format,
+            (None, Some(output)) => Format::from_extension(output).unwrap_or(Format::Srt),
+            (None, None) if retime.is_some() => Format::Sup,
+            (None, None) => Format::Srt,
+        };
+        if let Some(feature) = format.missing_feature() {
+            return Err(format!(
+                "this format needs cap-parser built with the {} feature",
+                feature
+            ));
+        }
+        if retime.is_some() && format != Format::Sup {
+            return Err("retiming writes a .sup, use --format sup".to_string());
+        }
+
+        let output = match output {
+            Some(output) => output,
+            None if input == Path::new(STDIO) => PathBuf::from(STDIO),
+            None => input.with_extension(format.extension()),
+        };
+        if output == Path::new(STDIO) && format.writes_files() {
+            return Err(
+                "this format is written as several files and needs an output path".to_string(),
+            );
+        }
+
+        let threads = self.threads.unwrap_or_else(num_cpus::get);
+        if threads == 0 {
+            return Err("--threads needs at least 1".to_string());
+        }
+
+        let (review_threshold, review_images) = (self.review_threshold, self.review_images);
+        let review = self.review.map(|path| ReviewOptions {
+            threshold: review_threshold,
+            path,
+            image_dir: review_images,
+        });
+
+        let recognition = match self.glyph_table {
+            Some(table) => Recognition::Glyphs { table: Some(table) },
+            None if self.glyphs => Recognition::Glyphs { table: None },
+            None => Recognition::Tesseract,
+        };
+
+        let verbosity = if self.quiet { -1 } else { self.verbose as i8 };
+        let mut converter = Converter::builder()
+            .language(self.language.as_str())
+            .threads(threads)
+            .recognition(recognition)
+            .merge_repeated(self.merge_repeated)
+            .verbosity(verbosity);
+        if let Some(review) = &review {
+            converter = converter.review(review.clone());
+        }
+        if let Some(dir) = self.cache_dir {
+            converter = converter.cache_dir(dir);
+        }
+        if let Some(rate) = self.snap_frames {
+            converter = converter.snap_frames(match rate {
+                Some(rate) => FrameSnap::Rate(rate),
+                None => FrameSnap::Stream,
+            });
+        }
+
+        Ok(Options {
+            input,
+            output,
+            format,
+            language: self.language,
+            verbosity,
+            converter: converter.build(),
+            confidence_path: self.confidence,
+            review,
+            retime,
+            line_ending: if self.crlf {
+                LineEnding::CrLf
+            } else {
+                LineEnding::Lf
+            },
+            bom: self.bom,
+            ttml_timing: if self.ttml_ticks {
+                TtmlTiming::Ticks
+            } else {
+                TtmlTiming::Clock
+            },
+            tv_system: if self.pal {
+                TvSystem::Pal
+            } else {
+                TvSystem::Ntsc
+            },
+        })
+    }
+}
+
+// the task from the command line. --help and --version print and exit here, errors come back as
+// the message to print
+pub fn task() -> Result<Task, String> {
+    let cli = match Cli::from_args_safe() {
+        Ok(cli) => cli,
+        Err(error) if !error.use_stderr() => error.exit(),
+        Err(error) => return Err(error.message),
+    };
+
+    cli.into_task()
+        .map_err(|message| format!("error: {}", message))
+}
+
+impl Cli {
+    fn into_task(self) -> Result<Task, String> {
+        match self.command {
+            // optional so subcommands can go in its place
+            None => {
+                let input = self
+                    .input
+                    .ok_or_else(|| "no input given, see --help".to_string())?;
+                Ok(Task::Convert(
+                    self.convert.into_options(input, self.output)?,
+                ))
+            }
+            Some(Command::Batch {
+                dir,
+                out_dir,
+                force,
+                convert,
+            }) => batch_task(dir, out_dir, force, convert),
+            Some(Command::Dump {
+                input,
+                output,
+                json,
+                summarize_rle,
+            }) => {
+                if json && !cfg!(feature = "serde") {
+                    return Err("--json needs cap-parser built with the serde feature".to_string());
+                }
+                Ok(Task::Dump(DumpOptions {
+                    input,
+                    output,
+                    json,
+                    summarize_rle,
+                }))
+            }
+            Some(Command::Inspect {
+                input,
+                output,
+                from,
+                to,
+                segments,
+            }) => Ok(Task::Inspect(InspectOptions {
+                input,
+                output,
+                from,
+                to,
+                segments: segments.iter().map(|s| s.to_uppercase()).collect(),
+            })),
+            Some(Command::Validate {
+                input,
+                output,
+                decoder_model,
+            }) => Ok(Task::Validate(ValidateOptions {
+                input,
+                output,
+                decoder_model,
+            })),
+            Some(Command::Repair {
+                input,
+                output,
+                quiet,
+            }) => {
+                let output = match output {
+                    Some(output) => output,
+                    None if input == Path::new(STDIO) => PathBuf::from(STDIO),
+                    None => input.with_extension("repaired.sup"),
+                };
+                Ok(Task::Repair(RepairOptions {
+                    input,
+                    output,
+                    verbosity: if quiet { -1 } else { 0 },
+                }))
+            }
+            Some(Command::Cut {
+                input,
+                output,
+                from,
+                to,
+            }) => Ok(Task::Cut(CutOptions {
+                input,
+                output,
+                from,
+                to,
+            })),
+            Some(Command::Merge { inputs, output }) => {
+                Ok(Task::Merge(MergeOptions { inputs, output }))
+            }
+        }
+    }
+}
+
+fn batch_task(
+    dir: PathBuf,
+    out_dir: Option<PathBuf>,
+    force: bool,
+    convert: ConvertArgs,
+) -> Result<Task, String> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    if convert.confidence.is_some() || convert.review.is_some() {
+        return Err(
+            "--confidence and --review write a single file and can't be used with batch"
+                .to_string(),
+        );
+    }
+
+    let options = convert.into_options(dir.clone(), None)?;
+    Ok(Task::Batch(BatchOptions {
+        root: dir,
+        out_dir,
+        force,
+        options,
+    }))
+}
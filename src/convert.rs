@@ -0,0 +1,206 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use threadpool::ThreadPool;
+
+use crate::ocr::OcrError;
+use crate::parser::parse::packets;
+use crate::parser::renderer::{FrameSnap, HandleError, PacketHandler, Screen};
+use crate::parser::types::Timestamp;
+use crate::report::ReviewOptions;
+
+/// Why a stream couldn't be converted.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the stream, the glyph table or the OCR cache failed.
+    Io(io::Error),
+    /// No PGS packet could be read at this byte offset of the stream.
+    Parse { offset: usize },
+    /// A display set couldn't be rendered.
+    Render(HandleError),
+    /// The text of these screens couldn't be read, each with its number (as in
+    /// [`ScreenText::number`](crate::ocr::ScreenText::number)) and why.
+    Ocr(Vec<(u32, OcrError)>),
+    /// Retiming moves the packet presented at this time before 0 or past what a timestamp can
+    /// hold.
+    Retime { pts: Timestamp },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Parse { offset } => write!(f, "no PGS packet at byte {}", offset),
+            Error::Render(error) => write!(f, "a display set can't be rendered, {:?}", error),
+            Error::Ocr(failed) => match failed.first() {
+                Some((number, error)) => write!(
+                    f,
+                    "the text of {} screens can't be read, screen {}: {}",
+                    failed.len(),
+                    number,
+                    error
+                ),
+                None => write!(f, "the text of a screen can't be read"),
+            },
+            Error::Retime { pts } => {
+                write!(f, "retiming moves the packet at {} out of range", pts)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<HandleError> for Error {
+    fn from(error: HandleError) -> Self {
+        Error::Render(error)
+    }
+}
+
+/// How the text of a screen is read.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Recognition {
+    /// Every screen is read by tesseract.
+    Tesseract,
+    /// Glyph shapes tesseract has read before are recognized from a cache, which can be kept
+    /// between runs (and corrected by hand) as a glyph table.
+    Glyphs { table: Option<PathBuf> },
+}
+
+/// Reads PGS streams into rendered screens or OCRed text. Made with [`Converter::builder`];
+/// clones share one OCR thread pool, and can convert streams at the same time.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "ocr"), allow(dead_code))]
+pub struct Converter {
+    pub(crate) language: String,
+    pub(crate) recognition: Recognition,
+    pub(crate) review: Option<ReviewOptions>,
+    pub(crate) merge_repeated: bool,
+    pub(crate) cache_dir: Option<PathBuf>,
+    pub(crate) snap_frames: Option<FrameSnap>,
+    pub(crate) verbosity: i8,
+    pub(crate) pool: ThreadPool,
+}
+
+/// Settings for a [`Converter`], everything starts at what the command line defaults to.
+pub struct ConverterBuilder {
+    language: String,
+    threads: usize,
+    recognition: Recognition,
+    review: Option<ReviewOptions>,
+    merge_repeated: bool,
+    cache_dir: Option<PathBuf>,
+    snap_frames: Option<FrameSnap>,
+    verbosity: i8,
+}
+
+impl ConverterBuilder {
+    /// The tesseract language, `eng` or `chi_sim`, or several joined by `+`. `eng` by default.
+    pub fn language(self, language: impl Into<String>) -> Self {
+        ConverterBuilder {
+            language: language.into(),
+            ..self
+        }
+    }
+
+    /// OCR threads, at least 1. The number of CPUs by default.
+    pub fn threads(self, threads: usize) -> Self {
+        ConverterBuilder { threads, ..self }
+    }
+
+    pub fn recognition(self, recognition: Recognition) -> Self {
+        ConverterBuilder {
+            recognition,
+            ..self
+        }
+    }
+
+    /// Saves the images of screens read with a low confidence so they can be checked by eye.
+    pub fn review(self, review: ReviewOptions) -> Self {
+        ConverterBuilder {
+            review: Some(review),
+            ..self
+        }
+    }
+
+    /// Joins consecutive screens showing the same bitmap into one.
+    pub fn merge_repeated(self, merge_repeated: bool) -> Self {
+        ConverterBuilder {
+            merge_repeated,
+            ..self
+        }
+    }
+
+    /// Keeps OCR results in this directory between runs.
+    pub fn cache_dir(self, dir: impl Into<PathBuf>) -> Self {
+        ConverterBuilder {
+            cache_dir: Some(dir.into()),
+            ..self
+        }
+    }
+
+    /// Moves screen begin and end times to video frame boundaries.
+    pub fn snap_frames(self, snap: FrameSnap) -> Self {
+        ConverterBuilder {
+            snap_frames: Some(snap),
+            ..self
+        }
+    }
+
+    /// -1 prints nothing, 0 errors that don't stop the conversion (the default), 2 also every
+    /// screen's text as it's read.
+    pub fn verbosity(self, verbosity: i8) -> Self {
+        ConverterBuilder { verbosity, ..self }
+    }
+
+    pub fn build(self) -> Converter {
+        Converter {
+            language: self.language,
+            recognition: self.recognition,
+            review: self.review,
+            merge_repeated: self.merge_repeated,
+            cache_dir: self.cache_dir,
+            snap_frames: self.snap_frames,
+            verbosity: self.verbosity,
+            pool: ThreadPool::new(self.threads),
+        }
+    }
+}
+
+impl Converter {
+    pub fn builder() -> ConverterBuilder {
+        ConverterBuilder {
+            language: "eng".to_string(),
+            threads: num_cpus::get(),
+            recognition: Recognition::Tesseract,
+            review: None,
+            merge_repeated: false,
+            cache_dir: None,
+            snap_frames: None,
+            verbosity: 0,
+        }
+    }
+
+    /// Every screen of the stream rendered, without any OCR.
+    pub fn render<R: Read>(&self, mut reader: R) -> Result<Vec<Screen>, Error> {
+        let mut pgs_buf = Vec::new();
+        reader.read_to_end(&mut pgs_buf)?;
+
+        let mut packet_handler = PacketHandler::new().with_snap(self.snap_frames);
+        let mut screens = Vec::new();
+        for packet in packets(&pgs_buf) {
+            if let Some(screen) = packet_handler.handle(packet?)? {
+                screens.push(screen);
+            }
+        }
+
+        Ok(screens)
+    }
+}
@@ -0,0 +1,89 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use cap_parser::parser::parse::packets;
+use cap_parser::parser::types::Packet;
+
+use crate::{create_output, read_input, Failure};
+
+pub struct DumpOptions {
+    // cli::STDIO for stdin
+    pub input: PathBuf,
+    // cli::STDIO for stdout
+    pub output: PathBuf,
+    // JSON rather than the Debug form of each packet
+    pub json: bool,
+    // object data as its size rather than base64
+    pub summarize_rle: bool,
+}
+
+// the packets read before a broken one are still written, then the error comes back
+pub fn run(options: &DumpOptions) -> Result<(), Failure> {
+    let buffer = read_input(&options.input)?;
+    let mut parsed = Vec::new();
+    let mut error = None;
+    for packet in packets(&buffer) {
+        match packet {
+            Ok(packet) => parsed.push(packet),
+            Err(e) => error = Some(e),
+        }
+    }
+
+    let mut out = create_output(&options.output)?;
+    if options.json {
+        write_json(&mut out, &parsed, options.summarize_rle)?;
+    } else {
+        for packet in &parsed {
+            writeln!(out, "{:?}", packet)?;
+        }
+    }
+    out.flush()?;
+
+    match error {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn write_json(out: &mut dyn Write, packets: &[Packet], summarize_rle: bool) -> io::Result<()> {
+    use cap_parser::parser::types::{RLEEntry, Segment};
+    use serde_json::json;
+
+    let mut value = serde_json::to_value(packets).map_err(io::Error::other)?;
+    if summarize_rle {
+        let values = value.as_array_mut().expect("packets serialize to an array");
+        for (packet, value) in packets.iter().zip(values) {
+            if let Segment::ObjectDefinition(ods) = &packet.segment {
+                let pixels: usize = ods
+                    .data_raw
+                    .iter()
+                    .map(|entry| match entry {
+                        RLEEntry::Single(_) => 1,
+                        RLEEntry::Repeated { count, .. } => *count as usize,
+                        RLEEntry::EndOfLine => 0,
+                    })
+                    .sum();
+                let lines = ods
+                    .data_raw
+                    .iter()
+                    .filter(|entry| **entry == RLEEntry::EndOfLine)
+                    .count();
+                value["segment"]["ObjectDefinition"]["data_raw"] = json!({
+                    "runs": ods.data_raw.len(),
+                    "pixels": pixels,
+                    "lines": lines,
+                });
+            }
+        }
+    }
+
+    serde_json::to_writer_pretty(&mut *out, &value).map_err(io::Error::other)?;
+    writeln!(out)
+}
+
+// refused by cli::task, there's no JSON without serde
+#[cfg(not(feature = "serde"))]
+fn write_json(_: &mut dyn Write, _: &[Packet], _: bool) -> io::Result<()> {
+    unreachable!("--json needs the serde feature")
+}
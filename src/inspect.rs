@@ -0,0 +1,337 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use cap_parser::parser::display_set::{display_sets, DisplaySet};
+use cap_parser::parser::parse::packets_at;
+use cap_parser::parser::types::{
+    CompositionObjectCrop, CompositionState, FrameRate, Packet, Segment, Timestamp,
+};
+
+use crate::{create_output, read_input, Failure};
+
+// the segment names --segments takes
+pub const SEGMENT_NAMES: [&str; 5] = ["pcs", "wds", "pds", "ods", "end"];
+
+pub struct InspectOptions {
+    // cli::STDIO for stdin
+    pub input: PathBuf,
+    // cli::STDIO for stdout
+    pub output: PathBuf,
+    // display sets starting before this are left out
+    pub from: Option<Timestamp>,
+    // display sets starting after this are left out
+    pub to: Option<Timestamp>,
+    // the segment types to print, upper case as Segment::name has them, all when empty
+    pub segments: Vec<String>,
+}
+
+// like dump, the packets read before a broken one are still printed, then the error comes back
+pub fn run(options: &InspectOptions) -> Result<(), Failure> {
+    let buffer = read_input(&options.input)?;
+    let mut parsed = Vec::new();
+    let mut error = None;
+    for packet in packets_at(&buffer) {
+        match packet {
+            Ok(packet) => parsed.push(packet),
+            Err(e) => error = Some(e),
+        }
+    }
+
+    let mut out = create_output(&options.output)?;
+    write_sets(&mut out, &display_sets(parsed), options)?;
+    out.flush()?;
+
+    match error {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+// an epoch header before the first set shown of each epoch, a header per display set, then its
+// packets one per line. Display sets and epochs are numbered from 1 across the whole stream
+// whatever the filters leave out, so the numbers stay the same between runs
+fn write_sets(
+    out: &mut dyn Write,
+    sets: &[DisplaySet],
+    options: &InspectOptions,
+) -> io::Result<()> {
+    let mut epoch = 0;
+    let mut epoch_shown = None;
+    let mut last_pts = Timestamp::default();
+    for (idx, set) in sets.iter().enumerate() {
+        if set.starts_epoch() {
+            epoch += 1;
+        }
+
+        // on the timeline past the 32 bit wrap, like the renderer does it
+        let times: Vec<(Timestamp, Timestamp)> = set
+            .packets
+            .iter()
+            .map(|(_, packet)| {
+                let pts = packet.pts.after(last_pts);
+                let dts = packet.dts.after(last_pts);
+                last_pts = pts;
+                (pts, dts)
+            })
+            .collect();
+
+        let set_pts = times.first().map_or(Timestamp::default(), |(pts, _)| *pts);
+        if options.from.is_some_and(|from| set_pts < from)
+            || options.to.is_some_and(|to| set_pts > to)
+        {
+            continue;
+        }
+
+        if epoch_shown != Some(epoch) {
+            match epoch {
+                0 => writeln!(out, "before the first epoch start")?,
+                _ => writeln!(out, "epoch {}", epoch)?,
+            }
+            epoch_shown = Some(epoch);
+        }
+        write!(out, "  display set {} at {}", idx + 1, set_pts)?;
+        match set.composition() {
+            Some(pcs) => {
+                // the video frame it's shown on, at the frame rate the composition gives
+                if let Some(rate) = FrameRate::from_pgs(pcs.frame_rate) {
+                    write!(out, " (frame {})", set_pts.as_frames(rate))?;
+                }
+                write!(out, ", composition {}", pcs.number)?
+            }
+            None => write!(out, ", no composition")?,
+        }
+        if !set.is_complete() {
+            write!(out, ", no END")?;
+        }
+        writeln!(out)?;
+
+        for ((offset, packet), (pts, dts)) in set.packets.iter().zip(times) {
+            let name = packet.segment.name();
+            if options.segments.is_empty() || options.segments.iter().any(|s| s == name) {
+                write_packet(out, *offset, packet, pts, dts)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_packet(
+    out: &mut dyn Write,
+    offset: usize,
+    packet: &Packet,
+    pts: Timestamp,
+    dts: Timestamp,
+) -> io::Result<()> {
+    write!(
+        out,
+        "    {:#010x}  {}  pts {}  dts {}",
+        offset,
+        packet.segment.name(),
+        pts,
+        dts
+    )?;
+
+    match &packet.segment {
+        Segment::PresentationComposition(pcs) => {
+            let rate = match FrameRate::from_pgs(pcs.frame_rate) {
+                Some(rate) => format!("{}fps", rate),
+                None => format!("frame rate {:#04x}", pcs.frame_rate),
+            };
+            let state = match pcs.state {
+                CompositionState::Normal => "normal",
+                CompositionState::AcquisitionPoint => "acquisition point",
+                CompositionState::EpochStart => "epoch start",
+            };
+            writeln!(
+                out,
+                "  {}x{} {}, number {}, {}, palette {}{}, {} objects",
+                pcs.width,
+                pcs.height,
+                rate,
+                pcs.number,
+                state,
+                pcs.palette_id,
+                if pcs.palette_update { " update" } else { "" },
+                pcs.objects.len()
+            )?;
+            for object in &pcs.objects {
+                write!(
+                    out,
+                    "          object {} in window {} at {},{}",
+                    object.id, object.window_id, object.x, object.y
+                )?;
+                if let CompositionObjectCrop::Cropped {
+                    x,
+                    y,
+                    width,
+                    height,
+                } = object.crop
+                {
+                    write!(out, ", cropped to {}x{} at {},{}", width, height, x, y)?;
+                }
+                writeln!(out)?;
+            }
+        }
+        Segment::WindowDefinition(windows) => {
+            writeln!(out, "  {} windows", windows.len())?;
+            for window in windows {
+                writeln!(
+                    out,
+                    "          window {} {}x{} at {},{}",
+                    window.id, window.width, window.height, window.x, window.y
+                )?;
+            }
+        }
+        Segment::PaletteDefinition(pds) => writeln!(
+            out,
+            "  palette {} version {}, {} entries",
+            pds.id,
+            pds.version,
+            pds.entries.len()
+        )?,
+        Segment::ObjectDefinition(ods) => {
+            let fragment = match (ods.is_first_in_sequence, ods.is_last_in_sequence) {
+                (true, true) => "whole",
+                (true, false) => "first fragment",
+                (false, true) => "last fragment",
+                (false, false) => "middle fragment",
+            };
+            writeln!(
+                out,
+                "  object {} version {}, {}x{}, {}, {} runs",
+                ods.id,
+                ods.version,
+                ods.width,
+                ods.height,
+                fragment,
+                ods.data_raw.len()
+            )?
+        }
+        Segment::End => writeln!(out)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cap_parser::parser::types::{
+        CompositionObject, ObjectDefinition, PresentationComposition, RLEEntry, WindowDefinition,
+    };
+
+    fn packet(pts_ms: u64, segment: Segment) -> Packet {
+        Packet {
+            pts: Timestamp::from_millis(pts_ms),
+            dts: Timestamp::from_millis(pts_ms.saturating_sub(10)),
+            segment,
+        }
+    }
+
+    fn pcs(number: u16, state: CompositionState, objects: Vec<CompositionObject>) -> Segment {
+        Segment::PresentationComposition(PresentationComposition {
+            width: 1920,
+            height: 1080,
+            frame_rate: 0x10,
+            number,
+            state,
+            palette_update: false,
+            palette_id: 0,
+            objects,
+        })
+    }
+
+    fn sets() -> Vec<DisplaySet> {
+        let object = CompositionObject {
+            id: 0,
+            window_id: 0,
+            x: 773,
+            y: 927,
+            crop: CompositionObjectCrop::NotCropped,
+        };
+        let packets = vec![
+            packet(1_000, pcs(0, CompositionState::EpochStart, vec![object])),
+            packet(
+                1_000,
+                Segment::WindowDefinition(vec![WindowDefinition {
+                    id: 0,
+                    x: 773,
+                    y: 927,
+                    width: 374,
+                    height: 60,
+                }]),
+            ),
+            packet(
+                1_000,
+                Segment::ObjectDefinition(ObjectDefinition {
+                    id: 0,
+                    version: 0,
+                    is_last_in_sequence: true,
+                    is_first_in_sequence: true,
+                    width: 374,
+                    height: 60,
+                    data_raw: vec![RLEEntry::Single(1), RLEEntry::EndOfLine],
+                }),
+            ),
+            packet(1_000, Segment::End),
+            packet(3_500, pcs(1, CompositionState::Normal, vec![])),
+            packet(3_500, Segment::End),
+        ];
+        display_sets(
+            packets
+                .into_iter()
+                .enumerate()
+                .map(|(idx, p)| (idx * 30, p)),
+        )
+    }
+
+    fn inspect(options: &InspectOptions) -> String {
+        let mut out = Vec::new();
+        write_sets(&mut out, &sets(), options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn options() -> InspectOptions {
+        InspectOptions {
+            input: PathBuf::from("-"),
+            output: PathBuf::from("-"),
+            from: None,
+            to: None,
+            segments: vec![],
+        }
+    }
+
+    #[test]
+    fn test_inspect() {
+        assert_eq!(
+            inspect(&options()),
+            "epoch 1\n  \
+             display set 1 at 00:00:01.000 (frame 23), composition 0\n    \
+             0x00000000  PCS  pts 00:00:01.000  dts 00:00:00.990  1920x1080 23.976fps, number 0, epoch start, palette 0, 1 objects\n          \
+             object 0 in window 0 at 773,927\n    \
+             0x0000001e  WDS  pts 00:00:01.000  dts 00:00:00.990  1 windows\n          \
+             window 0 374x60 at 773,927\n    \
+             0x0000003c  ODS  pts 00:00:01.000  dts 00:00:00.990  object 0 version 0, 374x60, whole, 2 runs\n    \
+             0x0000005a  END  pts 00:00:01.000  dts 00:00:00.990\n  \
+             display set 2 at 00:00:03.500 (frame 83), composition 1\n    \
+             0x00000078  PCS  pts 00:00:03.500  dts 00:00:03.490  1920x1080 23.976fps, number 1, normal, palette 0, 0 objects\n    \
+             0x00000096  END  pts 00:00:03.500  dts 00:00:03.490\n"
+        );
+    }
+
+    #[test]
+    fn test_inspect_filters() {
+        let options = InspectOptions {
+            from: Some(Timestamp::from_millis(2_000)),
+            segments: vec!["PCS".to_string()],
+            ..options()
+        };
+        assert_eq!(
+            inspect(&options),
+            "epoch 1\n  \
+             display set 2 at 00:00:03.500 (frame 83), composition 1\n    \
+             0x00000078  PCS  pts 00:00:03.500  dts 00:00:03.490  1920x1080 23.976fps, number 1, normal, palette 0, 0 objects\n"
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Reads Blu-ray PGS (.sup) subtitle streams: parsing packets, rendering display sets to images,
+//! reading their text with tesseract and writing text, image and DVD subtitle formats.
+//!
+//! ```no_run
+//! use std::fs::File;
+//!
+//! use cap_parser::subtitle::srt::SrtWriter;
+//! use cap_parser::subtitle::{SubtitleDocument, SubtitleWriter};
+//! use cap_parser::Converter;
+//!
+//! # #[cfg(feature = "ocr")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let converter = Converter::builder().language("eng").build();
+//! let texts = converter.convert(File::open("movie.sup")?)?;
+//! let document = SubtitleDocument::from_screens(&texts);
+//! SrtWriter::default().write(&document, &mut File::create("movie.srt")?)?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "ocr"))]
+//! # fn main() {}
+//! ```
+//!
+//! The lower levels can be used on their own: [`parser::parse::get_packet`] reads one packet,
+//! [`parser::renderer::PacketHandler`] turns packets into rendered screens.
+//!
+//! Cargo features: `ocr` (tesseract, for [`Converter::convert`]), `image` (PNG and TIFF encoding,
+//! for [`subtitle::ttml_image`]) and `jemalloc` (the command line's allocator), all on by default.
+//! Without them the parser, renderer, retiming and the VobSub output build on their own.
+
+#[macro_use]
+extern crate derivative;
+
+mod convert;
+mod hash;
+/// Reading the text of rendered screens.
+pub mod ocr;
+/// Reading, checking, fixing and writing PGS streams.
+pub mod parser;
+/// Confidence reports for OCR output.
+pub mod report;
+/// Moving and stretching the timestamps of a stream.
+pub mod retime;
+/// Text and image subtitle formats the screens are written as.
+pub mod subtitle;
+
+pub use crate::convert::{Converter, ConverterBuilder, Error, Recognition};
@@ -1,200 +1,291 @@
-#[macro_use]
-extern crate derivative;
-
 use fs::File;
+use std::fmt;
 use std::fs;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use cap_parser::report::ReviewOptions;
+use cap_parser::retime::{retime_stream, Retime};
+use cap_parser::subtitle::ttml::TtmlTiming;
+use cap_parser::subtitle::vobsub::{TvSystem, VobSubWriter};
+use cap_parser::subtitle::{self, LineEnding};
+use cap_parser::{Converter, Error};
 
-use image::ImageError;
-use leptess::capi;
-use leptess::tesseract::TessApi;
-use nom::error::VerboseError;
+#[cfg(feature = "image")]
+use cap_parser::subtitle::ttml_image;
+#[cfg(feature = "ocr")]
+use cap_parser::{
+    report,
+    subtitle::ass::AssWriter,
+    subtitle::srt::SrtWriter,
+    subtitle::ttml::TtmlWriter,
+    subtitle::vtt::VttWriter,
+    subtitle::{SubtitleDocument, SubtitleWriter},
+};
 
-use crate::parser::parse::get_packet;
-use crate::parser::renderer::{PacketHandler, Screen};
-use nom::lib::std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
-use threadpool::ThreadPool;
+use crate::cli::{Format, Task};
 
-#[cfg(not(target_env = "msvc"))]
+#[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
 use jemallocator::Jemalloc;
 
-#[cfg(not(target_env = "msvc"))]
+#[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
-mod parser;
+mod batch;
+mod cli;
+mod dump;
+mod inspect;
+mod repair;
+mod splice;
+mod validate;
 
 fn timeit<Ret, F: FnOnce() -> Ret>(f: F) -> Ret {
     let before = std::time::Instant::now();
     let result = f();
     let after = std::time::Instant::now();
-    println!("took {:?}", after - before);
+    eprintln!("took {:?}", after - before);
 
     result
 }
 
-fn main() -> std::io::Result<()> {
-    timeit(|| {
-        let mut f = File::open("subs.sup")?;
-        let mut buffer = Vec::with_capacity(f.metadata()?.len() as usize);
-        f.read_to_end(&mut buffer)?;
+// the text output settings are read only by builds with OCR
+#[derive(Clone)]
+#[cfg_attr(not(feature = "ocr"), allow(dead_code))]
+struct Options {
+    // cli::STDIO for stdin
+    input: PathBuf,
+    // cli::STDIO for stdout
+    output: PathBuf,
+    format: Format,
+    // tesseract language, also tagged on the outputs that carry one
+    language: String,
+    // -1 prints nothing, 0 errors, 1 timing and a summary, 2 every cue as it's read
+    verbosity: i8,
+    // reads the input, clones share its OCR threads
+    converter: Converter,
+    // machine readable per cue and per line OCR confidence
+    confidence_path: Option<PathBuf>,
+    review: Option<ReviewOptions>,
+    // applied when writing a .sup
+    retime: Option<Retime>,
+    line_ending: LineEnding,
+    bom: bool,
+    ttml_timing: TtmlTiming,
+    tv_system: TvSystem,
+}
 
-        let mut fout = File::create("subs.srt")?;
-        let text = do_parse(&buffer);
-        fout.write_all(text.as_bytes())?;
+// exit codes
+const EXIT_IO: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_BAD_INPUT: i32 = 3;
+const EXIT_BATCH: i32 = 4;
+const EXIT_INVALID: i32 = 5;
+const EXIT_OCR: i32 = 6;
 
-        Ok(())
-    })
+#[derive(Debug)]
+enum Failure {
+    // reading the input or writing an output failed
+    Io(io::Error),
+    // the input isn't a PGS stream that can be read
+    BadInput(Error),
+    // this many files of a batch weren't converted, each is listed in the summary
+    Batch(usize),
+    // the stream breaks the PGS rules this many times, each is listed in the report
+    Invalid(usize),
+    // tesseract couldn't read the text of a screen
+    Ocr(Error),
+    // the retime options move a packet before 0
+    Retime(Error),
 }
 
-fn do_parse(pgs_buf: &[u8]) -> String {
-    let mut packet_handler = PacketHandler::new();
-    let mut frame_number = 0;
-
-    let mut rest = pgs_buf;
-
-    let texts = Arc::new(Mutex::new(BTreeMap::new()));
-    let thread_pool = ThreadPool::new(num_cpus::get());
-    while !rest.is_empty() {
-        match get_packet::<VerboseError<&[u8]>>(rest) {
-            Ok((remains, packet)) => {
-                rest = remains;
-                if let Some(value) = handle_packet(
-                    &mut packet_handler,
-                    packet,
-                    &texts,
-                    &thread_pool,
-                    frame_number,
-                ) {
-                    return value;
-                }
-                frame_number += 1;
-            }
-            Err(error) => {
-                eprintln!("error! {:#?}\n", error);
-                return "error".to_string();
-            }
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Io(_) => EXIT_IO,
+            Failure::BadInput(_) => EXIT_BAD_INPUT,
+            Failure::Batch(_) => EXIT_BATCH,
+            Failure::Invalid(_) => EXIT_INVALID,
+            Failure::Ocr(_) => EXIT_OCR,
+            Failure::Retime(_) => EXIT_USAGE,
         }
     }
-    thread_pool.join();
-
-    let lines: Vec<String> = Arc::try_unwrap(texts)
-        .unwrap()
-        .into_inner()
-        .unwrap()
-        .into_values()
-        .collect();
-    lines.join("\n")
 }
 
-fn handle_packet(
-    packet_handler: &mut PacketHandler,
-    packet: parser::types::Packet,
-    texts: &Arc<Mutex<BTreeMap<u32, String>>>,
-    pool: &ThreadPool,
-    frame_number: u32,
-) -> Option<String> {
-    match packet_handler.handle(packet) {
-        Ok(image) => {
-            if let Some(img) = image {
-                let texts = Arc::clone(texts);
-                pool.execute(move || match get_text_from_screen(frame_number, &img) {
-                    Ok(text) => {
-                        if let Some(text) = text {
-                            dbg!(&text);
-                            texts.lock().unwrap().insert(frame_number, text);
-                        };
-                    }
-                    Err(error) => eprintln!("error {:#?}\n", error),
-                });
-            }
-        }
-        Err(error) => {
-            eprintln!("error! {:#?}\n", error);
-            return Some("error".to_string());
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Io(error) => write!(f, "{}", error),
+            Failure::BadInput(error) => write!(
+                f,
+                "the input is not a PGS stream that can be read, {}",
+                error
+            ),
+            Failure::Batch(failed) => write!(f, "{} files could not be converted", failed),
+            Failure::Invalid(violations) => write!(f, "PGS rule violations found: {}", violations),
+            Failure::Ocr(error) | Failure::Retime(error) => write!(f, "{}", error),
         }
     }
-    None
 }
 
-fn post_process_text(text: String) -> Option<String> {
-    let mut out = text;
-    out = out
-        .split("\n")
-        .map(|line| line.replace("\n", " "))
-        .map(|line| line.trim_start().trim_end().to_string())
-        .filter(|line| !line.is_empty())
-        .collect::<Vec<String>>()
-        .join("\n");
-
-    if out.is_empty() {
-        None
-    } else {
-        Some(out)
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Failure::Io(error)
     }
 }
 
-fn get_text_from_screen(frame_num: u32, screen: &Screen) -> Result<Option<String>, ImageError> {
-    // save the image to a temporary file
-    let temp_subtitle_image_file_path = format!("tmp/sub-{}.tiff", frame_num);
-    screen.image.save(&temp_subtitle_image_file_path)?;
-    let pix = leptess::leptonica::pix_read(Path::new(&temp_subtitle_image_file_path)).unwrap();
-
-    // TODO: thread local storage would probably be beneficial here to avoid recreating the API
-    // object for every screen
-    const LANG: &str = "eng";
-    let mut tesseract_api = TessApi::new(None, LANG).unwrap();
-    let ptr = unsafe { std::ptr::read(&tesseract_api as *const _ as *const usize) as *mut _ };
-    unsafe {
-        capi::TessBaseAPISetPageSegMode(ptr, leptess::capi::TessPageSegMode_PSM_SPARSE_TEXT_OSD);
-    };
-    tesseract_api.set_image(&pix);
-    unsafe {
-        capi::TessBaseAPISetSourceResolution(ptr, 120);
+impl From<Error> for Failure {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(error) => Failure::Io(error),
+            error @ Error::Ocr(_) => Failure::Ocr(error),
+            error @ Error::Retime { .. } => Failure::Retime(error),
+            error => Failure::BadInput(error),
+        }
     }
+}
 
-    let text = post_process_text(tesseract_api.get_utf8_text().unwrap());
-    match fs::remove_file(&temp_subtitle_image_file_path) {
-        Ok(_) => {}
-        Err(err) => {
-            eprintln!(
-                "error deleting frame temp file {} -> {}",
-                temp_subtitle_image_file_path, err
-            )
+fn main() {
+    let task = match cli::task() {
+        Ok(task) => task,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(EXIT_USAGE);
         }
+    };
+
+    let verbosity = match &task {
+        Task::Convert(options) => options.verbosity,
+        Task::Batch(batch) => batch.options.verbosity,
+        Task::Dump(_) | Task::Inspect(_) | Task::Validate(_) | Task::Cut(_) | Task::Merge(_) => 0,
+        Task::Repair(repair) => repair.verbosity,
+    };
+    let run = || match &task {
+        Task::Convert(options) => convert_one(options),
+        Task::Batch(batch) => batch::run(batch),
+        Task::Dump(dump) => dump::run(dump),
+        Task::Inspect(inspect) => inspect::run(inspect),
+        Task::Validate(validate) => validate::run(validate),
+        Task::Repair(repair) => repair::run(repair),
+        Task::Cut(cut) => splice::run_cut(cut),
+        Task::Merge(merge) => splice::run_merge(merge),
+    };
+    let result = if verbosity > 0 { timeit(run) } else { run() };
+
+    if let Err(failure) = result {
+        if verbosity >= 0 {
+            eprintln!("error: {}", failure);
+        }
+        process::exit(failure.exit_code());
     }
+}
+
+fn convert_one(options: &Options) -> Result<(), Failure> {
+    let cues = convert(options)?;
+    if let (Some(cues), true) = (cues, options.verbosity > 0) {
+        eprintln!("{} cues written to {}", cues, options.output.display());
+    }
+
+    Ok(())
+}
+
+// the number of cues written, None for a retimed .sup which is written back without rendering
+fn convert(options: &Options) -> Result<Option<usize>, Failure> {
+    let buffer = read_input(&options.input)?;
 
-    Ok(text.map(|data| {
-        format!(
-            "{}\n{} --> {}\n{}\n\n",
-            frame_num + 1,
-            format_timestamp_microsec(screen.begin_us),
-            format_timestamp_microsec(screen.begin_us + screen.dur_us),
-            data
-        )
-    }))
+    let cues = match options.format {
+        Format::Sup => {
+            // with nothing to retime the stream is only written back out
+            let retime = options.retime.unwrap_or(Retime::Shift(0));
+            let retimed = retime_stream(&buffer, &retime)?;
+            let mut out = create_output(&options.output)?;
+            out.write_all(&retimed)?;
+            out.flush()?;
+            return Ok(None);
+        }
+        // bitmap outputs skip OCR altogether
+        #[cfg(feature = "image")]
+        Format::TtmlImage => {
+            let screens = options.converter.render(&buffer[..])?;
+            ttml_image::write_imsc1_images(&screens, &options.output).map_err(io::Error::other)?;
+            screens.len()
+        }
+        Format::VobSub => {
+            let screens = options.converter.render(&buffer[..])?;
+            let writer = VobSubWriter {
+                system: options.tv_system,
+                language: subtitle::language_tag(&options.language),
+            };
+            writer.write(&screens, &options.output)?;
+            screens.len()
+        }
+        #[cfg(feature = "ocr")]
+        Format::Srt | Format::Vtt | Format::Ass | Format::Ttml => write_text(&buffer, options)?,
+        #[allow(unreachable_patterns)]
+        _ => unreachable!("cli::task only takes the formats this build can write"),
+    };
+
+    Ok(Some(cues))
 }
 
-fn format_timestamp_microsec(ms: u64) -> String {
-    const MS_PER_MICRO: u64 = 1_000;
-    const SEC_PER_MICRO: u64 = MS_PER_MICRO * 1_000;
-    const MIN_PER_MICRO: u64 = SEC_PER_MICRO * 60;
-    const HR_PER_MICRO: u64 = MIN_PER_MICRO * 60;
+// OCRs the screens into a text format, the reports go along with it
+#[cfg(feature = "ocr")]
+fn write_text(buffer: &[u8], options: &Options) -> Result<usize, Failure> {
+    let screens = options.converter.convert(buffer)?;
+    let document = SubtitleDocument::from_screens(&screens);
+    let mut out = create_output(&options.output)?;
+    writer_for(options).write(&document, &mut out)?;
+    out.flush()?;
 
-    let mut ms_remain = ms;
+    if let Some(path) = &options.confidence_path {
+        report::write_confidence_json(BufWriter::new(File::create(path)?), &screens)?;
+    }
 
-    let hours = ms_remain / HR_PER_MICRO;
-    ms_remain %= HR_PER_MICRO;
+    if let Some(review) = &options.review {
+        report::write_review(
+            BufWriter::new(File::create(&review.path)?),
+            review,
+            &screens,
+        )?;
+    }
 
-    let minutes = ms_remain / MIN_PER_MICRO;
-    ms_remain %= MIN_PER_MICRO;
+    Ok(document.cues.len())
+}
+
+fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    if path == Path::new(cli::STDIO) {
+        let mut buffer = Vec::new();
+        io::stdin().read_to_end(&mut buffer)?;
+        return Ok(buffer);
+    }
+
+    let mut f = File::open(path)?;
+    let mut buffer = Vec::with_capacity(f.metadata()?.len() as usize);
+    f.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
 
-    let seconds = ms_remain / SEC_PER_MICRO;
-    ms_remain %= SEC_PER_MICRO;
+fn create_output(path: &Path) -> io::Result<Box<dyn Write>> {
+    if path == Path::new(cli::STDIO) {
+        return Ok(Box::new(BufWriter::new(io::stdout())));
+    }
 
-    let milliseconds = ms_remain / MS_PER_MICRO;
+    Ok(Box::new(BufWriter::new(File::create(path)?)))
+}
 
-    format!("{}:{}:{},{}", hours, minutes, seconds, milliseconds)
+#[cfg(feature = "ocr")]
+fn writer_for(options: &Options) -> Box<dyn SubtitleWriter> {
+    match options.format {
+        Format::Vtt => Box::new(VttWriter),
+        Format::Ass => Box::new(AssWriter),
+        Format::Ttml => Box::new(TtmlWriter {
+            timing: options.ttml_timing,
+            language: subtitle::language_tag(&options.language),
+        }),
+        _ => Box::new(SrtWriter {
+            line_ending: options.line_ending,
+            bom: options.bom,
+        }),
+    }
 }
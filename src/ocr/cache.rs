@@ -0,0 +1,237 @@
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::hash::StableHasher;
+use crate::ocr::italic::Bounds;
+use crate::ocr::{OcrLine, OcrWord};
+use crate::parser::renderer::Screen;
+
+const HEADER: &str = "# cap-parser ocr cache v2";
+
+// numbers the temporary files of one process, conversions running side by side can put the same
+// screen at once
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// OCR results stored on disk, one file per screen, so reruns over the same stream only OCR what
+/// they haven't seen yet. The engine, language and settings are part of every key, changing any
+/// of them misses the old entries rather than returning stale text.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+    config_hash: u64,
+}
+
+impl DiskCache {
+    /// Creates `dir` if it's missing. `config` names the engine, language and settings.
+    pub fn new(dir: PathBuf, config: &str) -> io::Result<DiskCache> {
+        fs::create_dir_all(&dir)?;
+
+        let mut hasher = StableHasher::new();
+        hasher.write(config.as_bytes());
+        Ok(DiskCache {
+            dir,
+            config_hash: hasher.finish(),
+        })
+    }
+
+    fn path(&self, screen: &Screen) -> PathBuf {
+        let mut hasher = StableHasher::new();
+        hasher.write_u64(self.config_hash);
+        hasher.write_u64(screen.hash);
+        hasher.write_u32(screen.image.width());
+        hasher.write_u32(screen.image.height());
+        self.dir.join(format!("{:016x}.tsv", hasher.finish()))
+    }
+
+    /// Missing and unreadable entries are both treated as not cached.
+    pub fn get(&self, screen: &Screen) -> Option<Vec<OcrLine>> {
+        let file = File::open(self.path(screen)).ok()?;
+        let mut rows = BufReader::new(file).lines();
+        if rows.next()?.ok()? != HEADER {
+            return None;
+        }
+
+        let mut lines: Vec<OcrLine> = Vec::new();
+        for row in rows {
+            let row = row.ok()?;
+            let cols: Vec<&str> = row.splitn(8, '\t').collect();
+            if cols.len() != 8 {
+                return None;
+            }
+
+            let line_idx = cols[0].parse::<usize>().ok()?;
+            let word = OcrWord {
+                bounds: Bounds {
+                    left: cols[1].parse().ok()?,
+                    top: cols[2].parse().ok()?,
+                    width: cols[3].parse().ok()?,
+                    height: cols[4].parse().ok()?,
+                },
+                confidence: cols[5].parse().ok()?,
+                italic: cols[6] == "1",
+                text: unescape(cols[7])?,
+            };
+
+            if line_idx == lines.len() {
+                lines.push(OcrLine { words: Vec::new() });
+            }
+            lines.get_mut(line_idx)?.words.push(word);
+        }
+
+        Some(lines)
+    }
+
+    /// Written to a temporary file and renamed into place, an interrupted run never leaves a
+    /// half written entry behind.
+    pub fn put(&self, screen: &Screen, lines: &[OcrLine]) -> io::Result<()> {
+        let path = self.path(screen);
+        let temp_path = path.with_extension(format!(
+            "tmp-{}-{}",
+            std::process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        {
+            let mut out = BufWriter::new(File::create(&temp_path)?);
+            writeln!(out, "{}", HEADER)?;
+            for (line_idx, line) in lines.iter().enumerate() {
+                for word in &line.words {
+                    writeln!(
+                        out,
+                        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                        line_idx,
+                        word.bounds.left,
+                        word.bounds.top,
+                        word.bounds.width,
+                        word.bounds.height,
+                        word.confidence,
+                        word.italic as u8,
+                        escape(&word.text)
+                    )?;
+                }
+            }
+            out.flush()?;
+        }
+
+        fs::rename(&temp_path, &path)
+    }
+}
+
+// the text is the last column, so tabs in it can stay, but a row can't hold a line break
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => out.push('\\'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn screen(hash: u64) -> Screen {
+        Screen {
+            image: RgbaImage::new(200, 40),
+            begin_us: 0,
+            dur_us: 1_000_000,
+            x: 0,
+            y: 0,
+            video_width: 1920,
+            video_height: 1080,
+            windows: vec![],
+            hash,
+        }
+    }
+
+    fn word(text: &str, left: u32) -> OcrWord {
+        OcrWord {
+            text: text.to_string(),
+            italic: left > 0,
+            confidence: 87.5,
+            bounds: Bounds {
+                left,
+                top: 2,
+                width: 30,
+                height: 20,
+            },
+        }
+    }
+
+    // a cache directory of its own for each test
+    fn dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("cap-parser-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = dir("round-trip");
+        let cache = DiskCache::new(dir.clone(), "tesseract eng").unwrap();
+        let lines = vec![
+            OcrLine {
+                words: vec![word("tab\there", 0), word("a\\b\nc\r", 40)],
+            },
+            OcrLine {
+                words: vec![word("\\n", 0)],
+            },
+        ];
+
+        assert_eq!(cache.get(&screen(1)), None);
+        cache.put(&screen(1), &lines).unwrap();
+        assert_eq!(cache.get(&screen(1)), Some(lines));
+        assert_eq!(cache.get(&screen(2)), None);
+
+        // other settings never see the entries
+        let other = DiskCache::new(dir.clone(), "tesseract deu").unwrap();
+        assert_eq!(other.get(&screen(1)), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_entries() {
+        let dir = dir("unreadable");
+        let cache = DiskCache::new(dir.clone(), "tesseract eng").unwrap();
+        cache.put(&screen(1), &[]).unwrap();
+        assert_eq!(cache.get(&screen(1)), Some(vec![]));
+        let path = cache.path(&screen(1));
+
+        for entry in [
+            "# cap-parser ocr cache v1\n0\t0\t2\t30\t20\t87.5\t0\tword\n",
+            "0\t0\t2\t30\t20\t87.5\t0\tword\n",
+            "# cap-parser ocr cache v2\n0\t0\t2\t30\t20\t87.5\t0\n",
+            "# cap-parser ocr cache v2\n0\tleft\t2\t30\t20\t87.5\t0\tword\n",
+            "# cap-parser ocr cache v2\n3\t0\t2\t30\t20\t87.5\t0\tword\n",
+            "# cap-parser ocr cache v2\n0\t0\t2\t30\t20\t87.5\t0\tword\\\n",
+        ] {
+            fs::write(&path, entry).unwrap();
+            assert_eq!(cache.get(&screen(1)), None, "{:?}", entry);
+        }
+        fs::write(&path, b"# cap-parser ocr cache v2\n\xff\xfe\n").unwrap();
+        assert_eq!(cache.get(&screen(1)), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,756 @@
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+#[cfg(feature = "ocr")]
+use std::sync::RwLock;
+
+use image::{ImageError, Luma, RgbaImage};
+
+use crate::hash::StableHasher;
+use crate::ocr::italic::Bounds;
+use crate::ocr::{OcrLine, OcrWord};
+#[cfg(feature = "ocr")]
+use crate::{
+    ocr::{recognize_screen, OcrError},
+    parser::renderer::Screen,
+};
+
+// Subtitle tracks are drawn in one font, so the same glyph bitmaps turn up thousands of times.
+// Each screen is cut into glyphs, and once tesseract has read every glyph shape on a screen at
+// least once the text is put together from the cache without running tesseract at all. Screens
+// with any unknown glyph still go through tesseract (single glyphs OCR badly on their own), and
+// what it reads is used to learn the new shapes.
+
+const MIN_ALPHA: u8 = 0x80;
+// opaque pixels only count as glyph fill when brighter than the outline by at least this much
+const MIN_OUTLINE_CONTRAST: u8 = 64;
+// row bands shorter than this share of the tallest band are dots or accents of a neighbour
+const MIN_BAND_SHARE: f32 = 0.4;
+// components overlapping horizontally by this share of the narrower one belong to one glyph
+const MIN_GLYPH_OVERLAP: f32 = 0.5;
+// gap between glyphs, relative to the line height, that separates words until one is learned
+const DEFAULT_SPACE_RATIO: f32 = 0.25;
+// glyph shapes are compared scaled to a square of this many pixels a side
+const GLYPH_CELL: u32 = 16;
+// steps of the line height glyph heights are compared in, so x and X don't share a key
+const SIZE_STEPS: f32 = 6.0;
+// steps of a glyph's width to height it's compared in, so - and | don't share a key
+const ASPECT_STEPS: f32 = 4.0;
+
+/// Hash of a glyph's shape scaled to a square, with its size relative to the line height.
+pub type GlyphKey = u64;
+
+/// A connected shape of opaque pixels, usually one character.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Glyph {
+    pub key: GlyphKey,
+    pub bounds: Bounds,
+    mask: Vec<bool>,
+}
+
+/// Glyphs sharing a line, left to right.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GlyphLine {
+    pub bounds: Bounds,
+    pub glyphs: Vec<Glyph>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct GlyphEntry {
+    // (text, italic) -> number of times tesseract read the glyph that way
+    votes: HashMap<(String, bool), u32>,
+    // mappings loaded from a glyph table always win over what tesseract says
+    fixed: Option<(String, bool)>,
+    width: u32,
+    height: u32,
+    mask: Vec<bool>,
+}
+
+impl GlyphEntry {
+    fn best(&self) -> Option<(&str, bool, f32)> {
+        if let Some((text, italic)) = &self.fixed {
+            return Some((text, *italic, 100.0));
+        }
+
+        let total: u32 = self.votes.values().sum();
+        self.votes
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|((text, italic), count)| {
+                (text.as_str(), *italic, 100.0 * *count as f32 / total as f32)
+            })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct RunningMean {
+    sum: f32,
+    n: u32,
+}
+
+impl RunningMean {
+    fn add(&mut self, value: f32) {
+        self.sum += value;
+        self.n += 1;
+    }
+
+    fn mean(&self) -> Option<f32> {
+        if self.n == 0 {
+            None
+        } else {
+            Some(self.sum / self.n as f32)
+        }
+    }
+}
+
+/// What tesseract read each glyph as, so screens made only of known glyphs skip it.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct GlyphCache {
+    entries: HashMap<GlyphKey, GlyphEntry>,
+    // gaps between glyphs relative to line height, inside words and between words
+    letter_gaps: RunningMean,
+    word_gaps: RunningMean,
+}
+
+impl GlyphCache {
+    /// An empty cache.
+    pub fn new() -> GlyphCache {
+        GlyphCache::default()
+    }
+
+    fn space_ratio(&self) -> f32 {
+        match (self.letter_gaps.mean(), self.word_gaps.mean()) {
+            (Some(letter), Some(word)) => (letter + word) / 2.0,
+            _ => DEFAULT_SPACE_RATIO,
+        }
+    }
+
+    /// None as soon as a single glyph on the screen hasn't been seen before.
+    pub fn recognize(&self, lines: &[GlyphLine]) -> Option<Vec<OcrLine>> {
+        let space_ratio = self.space_ratio();
+        let mut out = Vec::with_capacity(lines.len());
+        for line in lines {
+            let mut words: Vec<Vec<(&Glyph, &str, bool, f32)>> = Vec::new();
+            let mut prev_right: Option<u32> = None;
+            for glyph in &line.glyphs {
+                let (text, italic, confidence) = self.entries.get(&glyph.key)?.best()?;
+                let new_word = match prev_right {
+                    Some(right) => {
+                        let gap = glyph.bounds.left.saturating_sub(right);
+                        gap as f32 / line.bounds.height as f32 > space_ratio
+                    }
+                    None => true,
+                };
+
+                if new_word {
+                    words.push(Vec::new());
+                }
+                words
+                    .last_mut()
+                    .unwrap()
+                    .push((glyph, text, italic, confidence));
+                prev_right = Some(glyph.bounds.left + glyph.bounds.width);
+            }
+
+            out.push(OcrLine {
+                words: words
+                    .into_iter()
+                    .map(|glyphs| {
+                        let italic_count = glyphs.iter().filter(|g| g.2).count();
+                        OcrWord {
+                            text: glyphs.iter().map(|g| g.1).collect(),
+                            italic: italic_count * 2 > glyphs.len(),
+                            confidence: glyphs.iter().map(|g| g.3).sum::<f32>()
+                                / glyphs.len() as f32,
+                            bounds: glyphs[1..]
+                                .iter()
+                                .fold(glyphs[0].0.bounds, |acc, g| acc.union(&g.0.bounds)),
+                        }
+                    })
+                    .collect(),
+            });
+        }
+
+        Some(out)
+    }
+
+    /// Words are only learned from when tesseract's reading has exactly one character per glyph.
+    pub fn learn(&mut self, lines: &[GlyphLine], ocr: &[OcrLine]) {
+        for line in ocr {
+            let mut prev_word_right: Option<(u32, u32)> = None;
+            for word in &line.words {
+                let glyph_line = match lines
+                    .iter()
+                    .find(|l| contains_center(&l.bounds, &word.bounds))
+                {
+                    Some(l) => l,
+                    None => continue,
+                };
+
+                let glyphs: Vec<&Glyph> = glyph_line
+                    .glyphs
+                    .iter()
+                    .filter(|g| contains_center(&word.bounds, &g.bounds))
+                    .collect();
+
+                let chars: Vec<char> = word.text.chars().collect();
+                if glyphs.is_empty() || glyphs.len() != chars.len() {
+                    prev_word_right = None;
+                    continue;
+                }
+
+                let height = glyph_line.bounds.height as f32;
+                if let Some((right, prev_line_top)) = prev_word_right {
+                    if prev_line_top == glyph_line.bounds.top {
+                        let gap = glyphs[0].bounds.left.saturating_sub(right);
+                        self.word_gaps.add(gap as f32 / height);
+                    }
+                }
+
+                for (idx, (glyph, c)) in glyphs.iter().zip(chars).enumerate() {
+                    if idx > 0 {
+                        let prev = glyphs[idx - 1];
+                        let gap = glyph
+                            .bounds
+                            .left
+                            .saturating_sub(prev.bounds.left + prev.bounds.width);
+                        self.letter_gaps.add(gap as f32 / height);
+                    }
+
+                    let entry = self.entries.entry(glyph.key).or_insert_with(|| GlyphEntry {
+                        votes: HashMap::new(),
+                        fixed: None,
+                        width: glyph.bounds.width,
+                        height: glyph.bounds.height,
+                        mask: glyph.mask.clone(),
+                    });
+                    *entry.votes.entry((c.to_string(), word.italic)).or_insert(0) += 1;
+                }
+
+                let last = glyphs[glyphs.len() - 1];
+                prev_word_right =
+                    Some((last.bounds.left + last.bounds.width, glyph_line.bounds.top));
+            }
+        }
+    }
+
+    /// One glyph per row: key (hex), italic (0 or 1), text. Rows can be edited by hand to correct
+    /// a mapping, and every row loaded from a table overrides what tesseract reads.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<GlyphCache> {
+        let mut cache = GlyphCache::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+
+            let cols: Vec<&str> = line.splitn(3, '\t').collect();
+            let parsed = if cols.len() == 3 {
+                GlyphKey::from_str_radix(cols[0], 16)
+                    .ok()
+                    .map(|key| (key, cols[1] == "1", cols[2].to_string()))
+            } else {
+                None
+            };
+
+            match parsed {
+                Some((key, italic, text)) => {
+                    cache.entries.insert(
+                        key,
+                        GlyphEntry {
+                            votes: HashMap::new(),
+                            fixed: Some((text, italic)),
+                            width: 0,
+                            height: 0,
+                            mask: Vec::new(),
+                        },
+                    );
+                }
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("bad glyph table row {:?}", line),
+                    ))
+                }
+            }
+        }
+
+        Ok(cache)
+    }
+
+    /// Writes the table, plus a png of every glyph named after its key so rows can be checked.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        image_dir: Option<&Path>,
+    ) -> Result<(), ImageError> {
+        let mut keys: Vec<&GlyphKey> = self.entries.keys().collect();
+        keys.sort();
+
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "# key\titalic\ttext")?;
+        for key in keys {
+            let entry = &self.entries[key];
+            if let Some((text, italic, _)) = entry.best() {
+                writeln!(out, "{:016x}\t{}\t{}", key, italic as u8, text)?;
+            }
+
+            if let Some(dir) = image_dir {
+                if entry.mask.is_empty() {
+                    continue;
+                }
+
+                fs::create_dir_all(dir)?;
+                let image = image::ImageBuffer::from_fn(entry.width, entry.height, |x, y| {
+                    if entry.mask[(y * entry.width + x) as usize] {
+                        Luma([0u8])
+                    } else {
+                        Luma([255u8])
+                    }
+                });
+                image.save(dir.join(format!("{:016x}.png", key)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the screen from the cache when every glyph is known, otherwise with tesseract, teaching
+/// the cache what it read.
+#[cfg(feature = "ocr")]
+pub fn recognize_screen_with_glyphs(
+    screen: &Screen,
+    cache: &RwLock<GlyphCache>,
+    language: &str,
+) -> Result<Vec<OcrLine>, OcrError> {
+    let lines = segment(&screen.image);
+    if let Some(known) = cache.read().unwrap().recognize(&lines) {
+        return Ok(known);
+    }
+
+    let ocr = recognize_screen(screen, language)?;
+    cache.write().unwrap().learn(&lines, &ocr);
+    Ok(ocr)
+}
+
+fn contains_center(outer: &Bounds, inner: &Bounds) -> bool {
+    let cx = inner.left + inner.width / 2;
+    let cy = inner.top + inner.height / 2;
+    cx >= outer.left
+        && cx < outer.left + outer.width
+        && cy >= outer.top
+        && cy < outer.top + outer.height
+}
+
+struct Mask {
+    width: u32,
+    height: u32,
+    bits: Vec<bool>,
+}
+
+impl Mask {
+    fn get(&self, x: u32, y: u32) -> bool {
+        self.bits[(y * self.width + x) as usize]
+    }
+}
+
+// subtitle text is usually a bright fill inside a dark outline. Only the fill is kept when there
+// is an outline, it keeps neighbouring letters apart where the outlines would touch
+fn glyph_mask(image: &RgbaImage) -> Mask {
+    let luma = |p: &image::Rgba<u8>| {
+        ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8
+    };
+
+    let opaque = image.pixels().filter(|p| p[3] >= MIN_ALPHA);
+    let (lo, hi) = opaque.fold((255u8, 0u8), |(lo, hi), p| {
+        let l = luma(p);
+        (min(lo, l), max(hi, l))
+    });
+
+    let threshold = if hi > lo && hi - lo >= MIN_OUTLINE_CONTRAST {
+        lo + (hi - lo) / 2
+    } else {
+        0
+    };
+
+    Mask {
+        width: image.width(),
+        height: image.height(),
+        bits: image
+            .pixels()
+            .map(|p| p[3] >= MIN_ALPHA && luma(p) >= threshold)
+            .collect(),
+    }
+}
+
+// horizontal runs of rows containing glyph pixels, with dots and accents folded into the line
+// they belong to
+fn row_bands(mask: &Mask) -> Vec<(u32, u32)> {
+    let mut bands: Vec<(u32, u32)> = Vec::new();
+    let mut start: Option<u32> = None;
+    for y in 0..mask.height {
+        let filled = (0..mask.width).any(|x| mask.get(x, y));
+        match (filled, start) {
+            (true, None) => start = Some(y),
+            (false, Some(s)) => {
+                bands.push((s, y));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        bands.push((s, mask.height));
+    }
+
+    loop {
+        let tallest = bands
+            .iter()
+            .map(|(top, bottom)| bottom - top)
+            .max()
+            .unwrap_or(0);
+        let small = bands
+            .iter()
+            .position(|(top, bottom)| ((bottom - top) as f32) < tallest as f32 * MIN_BAND_SHARE);
+        let idx = match small {
+            Some(idx) if bands.len() > 1 => idx,
+            _ => return bands,
+        };
+
+        let gap_above = idx.checked_sub(1).map(|prev| bands[idx].0 - bands[prev].1);
+        let gap_below = bands.get(idx + 1).map(|next| next.0 - bands[idx].1);
+        let into = match (gap_above, gap_below) {
+            (Some(above), Some(below)) if above < below => idx - 1,
+            (Some(_), None) => idx - 1,
+            _ => idx + 1,
+        };
+
+        let merged = (
+            min(bands[idx].0, bands[into].0),
+            max(bands[idx].1, bands[into].1),
+        );
+        bands[into] = merged;
+        bands.remove(idx);
+    }
+}
+
+struct Component {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+    pixels: Vec<(u32, u32)>,
+}
+
+fn band_components(mask: &Mask, top: u32, bottom: u32) -> Vec<Component> {
+    let width = mask.width;
+    let mut seen = vec![false; (width * (bottom - top)) as usize];
+    let idx = |x: u32, y: u32| ((y - top) * width + x) as usize;
+
+    let mut out = Vec::new();
+    for y in top..bottom {
+        for x in 0..width {
+            if !mask.get(x, y) || seen[idx(x, y)] {
+                continue;
+            }
+
+            seen[idx(x, y)] = true;
+            let mut stack = vec![(x, y)];
+            let mut comp = Component {
+                left: x,
+                top: y,
+                right: x + 1,
+                bottom: y + 1,
+                pixels: Vec::new(),
+            };
+            while let Some((px, py)) = stack.pop() {
+                comp.left = min(comp.left, px);
+                comp.top = min(comp.top, py);
+                comp.right = max(comp.right, px + 1);
+                comp.bottom = max(comp.bottom, py + 1);
+                comp.pixels.push((px, py));
+
+                for ny in py.saturating_sub(1)..min(py + 2, bottom) {
+                    for nx in px.saturating_sub(1)..min(px + 2, width) {
+                        if ny >= top && mask.get(nx, ny) && !seen[idx(nx, ny)] {
+                            seen[idx(nx, ny)] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+            }
+            out.push(comp);
+        }
+    }
+
+    out
+}
+
+/// Glyphs, left to right and line by line, of the rendered screen.
+pub fn segment(image: &RgbaImage) -> Vec<GlyphLine> {
+    let mask = glyph_mask(image);
+    row_bands(&mask)
+        .into_iter()
+        .map(|(top, bottom)| {
+            let mut components = band_components(&mask, top, bottom);
+            components.sort_by_key(|c| c.left);
+
+            let mut groups: Vec<Vec<Component>> = Vec::new();
+            for comp in components {
+                let joins = groups.last().is_some_and(|group| {
+                    let right = group.iter().map(|c| c.right).max().unwrap();
+                    let left = group.iter().map(|c| c.left).min().unwrap();
+                    let overlap = min(right, comp.right).saturating_sub(max(left, comp.left));
+                    let narrower = min(right - left, comp.right - comp.left);
+                    overlap as f32 >= narrower as f32 * MIN_GLYPH_OVERLAP
+                });
+
+                if joins {
+                    groups.last_mut().unwrap().push(comp);
+                } else {
+                    groups.push(vec![comp]);
+                }
+            }
+
+            let line_bounds = Bounds {
+                left: 0,
+                top,
+                width: mask.width,
+                height: bottom - top,
+            };
+            GlyphLine {
+                glyphs: groups
+                    .iter()
+                    .map(|group| make_glyph(group, &line_bounds))
+                    .collect(),
+                bounds: line_bounds,
+            }
+        })
+        .collect()
+}
+
+fn make_glyph(group: &[Component], line: &Bounds) -> Glyph {
+    let left = group.iter().map(|c| c.left).min().unwrap();
+    let top = group.iter().map(|c| c.top).min().unwrap();
+    let width = group.iter().map(|c| c.right).max().unwrap() - left;
+    let height = group.iter().map(|c| c.bottom).max().unwrap() - top;
+
+    let mut mask = vec![false; (width * height) as usize];
+    for (x, y) in group.iter().flat_map(|c| c.pixels.iter()) {
+        mask[((y - top) * width + (x - left)) as usize] = true;
+    }
+
+    // the same shape can mean different things depending on where it sits in the line, think
+    // of an apostrophe and a comma, or a hyphen and an underscore
+    let center = (top - line.top) as f32 + height as f32 / 2.0;
+    let placement = (3.0 * center / line.height as f32) as u8;
+    // the key leaves out the size in pixels, the same font at another video size or scaling
+    // finds the same glyphs
+    let size = (SIZE_STEPS * height as f32 / line.height as f32) as u8;
+    let aspect = (ASPECT_STEPS * width as f32 / height as f32)
+        .round()
+        .min(u8::MAX as f32) as u8;
+
+    let mut hasher = StableHasher::new();
+    hasher.write_u8(size);
+    hasher.write_u8(aspect);
+    hasher.write_u8(placement);
+    for chunk in scale_to_cell(&mask, width, height).chunks(8) {
+        hasher.write_u8(
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |acc, (bit, set)| acc | ((*set as u8) << bit)),
+        );
+    }
+
+    Glyph {
+        key: hasher.finish(),
+        bounds: Bounds {
+            left,
+            top,
+            width,
+            height,
+        },
+        mask,
+    }
+}
+
+// the mask resized to GLYPH_CELL x GLYPH_CELL, each cell pixel taking the mask pixel under its
+// center
+fn scale_to_cell(mask: &[bool], width: u32, height: u32) -> Vec<bool> {
+    let mut cell = Vec::with_capacity((GLYPH_CELL * GLYPH_CELL) as usize);
+    for cy in 0..GLYPH_CELL {
+        let y = (2 * cy + 1) * height / (2 * GLYPH_CELL);
+        for cx in 0..GLYPH_CELL {
+            let x = (2 * cx + 1) * width / (2 * GLYPH_CELL);
+            cell.push(mask[(y * width + x) as usize]);
+        }
+    }
+    cell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    // rectangles (x, y, width, height) of opaque white on a transparent image, scaled by `scale`
+    fn image(width: u32, height: u32, scale: u32, rects: &[(u32, u32, u32, u32)]) -> RgbaImage {
+        RgbaImage::from_fn(width * scale, height * scale, |x, y| {
+            let (x, y) = (x / scale, y / scale);
+            let inside = rects
+                .iter()
+                .any(|&(rx, ry, rw, rh)| x >= rx && x < rx + rw && y >= ry && y < ry + rh);
+            if inside {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    fn i_at(x: u32, y: u32) -> (u32, u32, u32, u32) {
+        (x, y, 2, 10)
+    }
+
+    fn l_at(x: u32, y: u32) -> [(u32, u32, u32, u32); 2] {
+        [(x, y, 2, 10), (x, y + 8, 6, 2)]
+    }
+
+    fn bounds(left: u32, top: u32, width: u32, height: u32) -> Bounds {
+        Bounds {
+            left,
+            top,
+            width,
+            height,
+        }
+    }
+
+    // "IL I" on one line, learned from a matching tesseract reading
+    fn learned() -> (Vec<GlyphLine>, GlyphCache) {
+        let mut rects = vec![i_at(5, 5), i_at(30, 5)];
+        rects.extend_from_slice(&l_at(8, 5));
+        let lines = segment(&image(40, 20, 1, &rects));
+
+        let word = |text: &str, bounds| OcrWord {
+            text: text.to_string(),
+            italic: false,
+            confidence: 90.0,
+            bounds,
+        };
+        let ocr = [OcrLine {
+            words: vec![
+                word("IL", bounds(5, 5, 9, 10)),
+                word("I", bounds(30, 5, 2, 10)),
+            ],
+        }];
+
+        let mut cache = GlyphCache::new();
+        assert_eq!(cache.recognize(&lines), None);
+        cache.learn(&lines, &ocr);
+        (lines, cache)
+    }
+
+    fn texts(lines: &[OcrLine]) -> Vec<String> {
+        lines.iter().map(|line| line.text()).collect()
+    }
+
+    #[test]
+    fn test_row_bands() {
+        // an accent over the first line, then a second line
+        let filled = |y: u32| y < 2 || (3..13).contains(&y) || (20..30).contains(&y);
+        let mask = Mask {
+            width: 4,
+            height: 32,
+            bits: (0..32).flat_map(|y| [filled(y); 4]).collect(),
+        };
+        assert_eq!(row_bands(&mask), vec![(0, 13), (20, 30)]);
+    }
+
+    #[test]
+    fn test_segment() {
+        let mut rects = vec![
+            i_at(5, 5),
+            // an i, its dot is part of the glyph
+            (30, 5, 2, 2),
+            (30, 9, 2, 6),
+            i_at(5, 30),
+        ];
+        rects.extend_from_slice(&l_at(15, 5));
+        let lines = segment(&image(40, 45, 1, &rects));
+
+        let glyph_bounds: Vec<Vec<Bounds>> = lines
+            .iter()
+            .map(|line| line.glyphs.iter().map(|glyph| glyph.bounds).collect())
+            .collect();
+        assert_eq!(
+            glyph_bounds,
+            vec![
+                vec![
+                    bounds(5, 5, 2, 10),
+                    bounds(15, 5, 6, 10),
+                    bounds(30, 5, 2, 10)
+                ],
+                vec![bounds(5, 30, 2, 10)],
+            ]
+        );
+        assert_eq!(lines[1].bounds, bounds(0, 30, 40, 10));
+
+        // the same shape in the same place on its line, different shapes
+        let key = |line: usize, glyph: usize| lines[line].glyphs[glyph].key;
+        assert_eq!(key(0, 0), key(1, 0));
+        assert_ne!(key(0, 0), key(0, 1));
+        assert_ne!(key(0, 0), key(0, 2));
+    }
+
+    #[test]
+    fn test_keys_ignore_scale() {
+        let key = |scale: u32, rects: &[(u32, u32, u32, u32)]| {
+            segment(&image(10, 14, scale, rects))[0].glyphs[0].key
+        };
+        assert_eq!(key(1, &l_at(2, 2)), key(2, &l_at(2, 2)));
+        assert_eq!(key(1, &[i_at(2, 2)]), key(3, &[i_at(2, 2)]));
+        // a bar on its side isn't the same glyph
+        assert_ne!(key(1, &[i_at(2, 2)]), key(1, &[(0, 2, 10, 2)]));
+    }
+
+    #[test]
+    fn test_learn_recognize() {
+        let (lines, cache) = learned();
+        let known = cache.recognize(&lines).unwrap();
+        assert_eq!(texts(&known), vec!["IL I"]);
+        assert_eq!(known[0].words[0].bounds, bounds(5, 5, 9, 10));
+        assert_eq!(known[0].words[1].confidence, 100.0);
+
+        // a glyph never seen sends the screen to tesseract
+        let mut rects = vec![i_at(5, 5), (10, 5, 4, 10)];
+        rects.extend_from_slice(&l_at(20, 5));
+        assert_eq!(cache.recognize(&segment(&image(40, 20, 1, &rects))), None);
+    }
+
+    #[test]
+    fn test_load_save() {
+        let (lines, cache) = learned();
+        let path = std::env::temp_dir().join(format!("cap-parser-glyphs-{}", std::process::id()));
+        cache.save(&path, None).unwrap();
+        let table = fs::read_to_string(&path).unwrap();
+        // the header, I and L
+        assert_eq!(table.lines().count(), 3);
+
+        // a corrected row
+        let edited = table.replace("\t0\tL", "\t1\tl");
+        fs::write(&path, format!("{}\n# a comment\n", edited)).unwrap();
+        let loaded = GlyphCache::load(&path).unwrap();
+        assert_eq!(texts(&loaded.recognize(&lines).unwrap()), vec!["Il I"]);
+
+        fs::write(&path, "not a row\n").unwrap();
+        let error = GlyphCache::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
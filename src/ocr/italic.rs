@@ -0,0 +1,159 @@
+use std::cmp::min;
+
+use image::RgbaImage;
+
+// Tesseract's LSTM engine doesn't report font attributes, so italics are found by shearing the
+// glyph pixels of a word and looking for the shear at which the vertical strokes line up best.
+// Shears are expressed as horizontal pixels per pixel of height (the tangent of the slant).
+const SHEAR_MIN: f32 = -0.1;
+const SHEAR_MAX: f32 = 0.5;
+const SHEAR_STEP: f32 = 0.02;
+
+// roughly 7 degrees, upright faces settle well below this and italic faces well above it
+const ITALIC_SHEAR: f32 = 0.12;
+
+const MIN_ALPHA: u8 = 0x80;
+const MIN_PIXELS: usize = 32;
+
+/// A rectangle of an image, in pixels.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Bounds {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Bounds {
+    /// The smallest rectangle covering both.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let left = min(self.left, other.left);
+        let top = min(self.top, other.top);
+        let right = (self.left + self.width).max(other.left + other.width);
+        let bottom = (self.top + self.height).max(other.top + other.height);
+
+        Bounds {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+}
+
+/// None when there are too few opaque pixels to tell.
+pub fn is_italic(image: &RgbaImage, bounds: Bounds) -> Option<bool> {
+    estimate_slant(image, bounds).map(|shear| shear >= ITALIC_SHEAR)
+}
+
+/// The horizontal shear that makes the strokes inside `bounds` most upright, 0 for upright text.
+/// None when there are too few opaque pixels to tell.
+pub fn estimate_slant(image: &RgbaImage, bounds: Bounds) -> Option<f32> {
+    let right = min(bounds.left + bounds.width, image.width());
+    let bottom = min(bounds.top + bounds.height, image.height());
+    if right <= bounds.left || bottom <= bounds.top {
+        return None;
+    }
+
+    // (x from the left edge, height above the bottom edge) of every opaque pixel
+    let mut pixels = Vec::new();
+    for y in bounds.top..bottom {
+        for x in bounds.left..right {
+            if image.get_pixel(x, y)[3] >= MIN_ALPHA {
+                pixels.push((x - bounds.left, bottom - 1 - y));
+            }
+        }
+    }
+
+    if pixels.len() < MIN_PIXELS {
+        return None;
+    }
+
+    let height = (bottom - bounds.top) as f32;
+    let offset = (SHEAR_MAX * height).ceil() as usize;
+    let columns =
+        (right - bounds.left) as usize + offset + (-SHEAR_MIN * height).ceil() as usize + 1;
+    let mut histogram = vec![0u32; columns];
+
+    let steps = ((SHEAR_MAX - SHEAR_MIN) / SHEAR_STEP).round() as usize;
+    let mut best: Option<(f32, u64)> = None;
+    for step in 0..=steps {
+        let shear = SHEAR_MIN + step as f32 * SHEAR_STEP;
+        histogram.iter_mut().for_each(|c| *c = 0);
+        for &(x, h) in &pixels {
+            // undo the lean, pixels further above the bottom edge are pushed back to the left
+            let column = x as f32 - shear * h as f32 + offset as f32;
+            histogram[column.round() as usize] += 1;
+        }
+
+        // aligned strokes pile up in few columns, which the sum of squares rewards
+        let score: u64 = histogram.iter().map(|&c| c as u64 * c as u64).sum();
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((shear, score));
+        }
+    }
+
+    best.map(|(shear, _)| shear)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const HEIGHT: u32 = 50;
+
+    // three vertical strokes leaning right by `shear`, inside the returned bounds
+    fn strokes(shear: f32) -> (RgbaImage, Bounds) {
+        let image = RgbaImage::from_fn(60, HEIGHT, |x, y| {
+            let lean = (shear * (HEIGHT - 1 - y) as f32).round() as u32;
+            if [5, 20, 35].iter().any(|left| x == left + lean) {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let bounds = Bounds {
+            left: 0,
+            top: 0,
+            width: image.width(),
+            height: HEIGHT,
+        };
+        (image, bounds)
+    }
+
+    #[test]
+    fn test_estimate_slant() {
+        for shear in [0.0, 0.1, 0.12, 0.14, 0.3] {
+            let (image, bounds) = strokes(shear);
+            let estimate = estimate_slant(&image, bounds).unwrap();
+            assert!(
+                (estimate - shear).abs() < 0.01,
+                "{} for {}",
+                estimate,
+                shear
+            );
+        }
+
+        let empty = RgbaImage::new(10, 10);
+        let bounds = Bounds {
+            left: 0,
+            top: 0,
+            width: 10,
+            height: 10,
+        };
+        assert_eq!(estimate_slant(&empty, bounds), None);
+    }
+
+    #[test]
+    fn test_is_italic() {
+        let italic = |shear| {
+            let (image, bounds) = strokes(shear);
+            is_italic(&image, bounds).unwrap()
+        };
+        assert!(!italic(0.0));
+        assert!(!italic(ITALIC_SHEAR - SHEAR_STEP));
+        assert!(italic(ITALIC_SHEAR));
+        assert!(italic(ITALIC_SHEAR + SHEAR_STEP));
+    }
+}
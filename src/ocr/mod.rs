@@ -0,0 +1,121 @@
+use std::fmt;
+use std::io;
+
+use image::ImageError;
+
+use crate::ocr::italic::Bounds;
+use crate::subtitle::{CueColors, Position};
+
+/// OCR results kept on disk between runs.
+pub mod cache;
+/// Glyphs tesseract has already read, recognized without it.
+pub mod glyph;
+/// Italic detection from the slant of the strokes.
+pub mod italic;
+#[cfg(feature = "ocr")]
+mod stream;
+#[cfg(feature = "ocr")]
+mod tesseract;
+
+#[cfg(feature = "ocr")]
+pub use crate::ocr::tesseract::{engine_config, recognize_screen};
+
+/// Why the text of a screen couldn't be read.
+#[derive(Debug)]
+pub enum OcrError {
+    /// The screen's image couldn't be prepared for tesseract, or saved for review.
+    Image(ImageError),
+    /// Writing the OCR cache failed.
+    Io(io::Error),
+    /// Tesseract couldn't be started (its data for the language is missing) or couldn't read the
+    /// image.
+    Tesseract(String),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::Image(error) => write!(f, "{}", error),
+            OcrError::Io(error) => write!(f, "{}", error),
+            OcrError::Tesseract(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+impl From<ImageError> for OcrError {
+    fn from(error: ImageError) -> Self {
+        OcrError::Image(error)
+    }
+}
+
+impl From<io::Error> for OcrError {
+    fn from(error: io::Error) -> Self {
+        OcrError::Io(error)
+    }
+}
+
+/// A word as tesseract read it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OcrWord {
+    pub text: String,
+    pub italic: bool,
+    /// Tesseract's confidence for the word, 0 to 100.
+    pub confidence: f32,
+    pub bounds: Bounds,
+}
+
+/// A line of words, top to bottom on the screen.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OcrLine {
+    pub words: Vec<OcrWord>,
+}
+
+impl OcrLine {
+    /// The words joined by single spaces.
+    pub fn text(&self) -> String {
+        self.words
+            .iter()
+            .map(|word| word.text.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Mean confidence of the words, 0 to 100.
+    pub fn confidence(&self) -> f32 {
+        mean_confidence(self.words.iter())
+    }
+}
+
+/// The text read from one screen, with when and where it's shown.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScreenText {
+    pub number: u32,
+    pub begin_us: u64,
+    pub end_us: u64,
+    /// Hash of the screen the text was read from.
+    pub hash: u64,
+    pub position: Position,
+    pub windows: Vec<Position>,
+    pub video_width: u32,
+    pub video_height: u32,
+    pub colors: CueColors,
+    pub lines: Vec<OcrLine>,
+}
+
+impl ScreenText {
+    /// Mean confidence of every word on the screen, 0 to 100.
+    pub fn confidence(&self) -> f32 {
+        mean_confidence(self.lines.iter().flat_map(|line| line.words.iter()))
+    }
+}
+
+fn mean_confidence<'a>(words: impl Iterator<Item = &'a OcrWord>) -> f32 {
+    let (sum, n) = words.fold((0.0, 0), |(sum, n), word| (sum + word.confidence, n + 1));
+    if n == 0 {
+        0.0
+    } else {
+        sum / n as f32
+    }
+}
@@ -0,0 +1,318 @@
+use std::cmp::max;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, RwLock};
+
+use threadpool::ThreadPool;
+
+use crate::ocr::cache::DiskCache;
+use crate::ocr::glyph::{self, GlyphCache};
+use crate::ocr::{self, OcrError, ScreenText};
+use crate::parser::parse::packets;
+use crate::parser::renderer::{PacketHandler, Screen};
+use crate::parser::types::Packet;
+use crate::report::ReviewOptions;
+use crate::subtitle::{self, Position};
+use crate::{Converter, Error, Recognition};
+
+// screens are only OCRed the first time a bitmap shows up, repeats (acquisition points, lines
+// said twice) reuse that result
+#[derive(Default)]
+struct Repeats {
+    // screen hash -> frame number of the first screen with that hash
+    seen: HashMap<u64, u32>,
+    repeats: Vec<Repeat>,
+}
+
+// a screen showing a bitmap seen before, maybe somewhere else on the frame
+struct Repeat {
+    frame_number: u32,
+    // frame number of the first screen with its hash
+    first: u32,
+    begin_us: u64,
+    end_us: u64,
+    position: Position,
+    windows: Vec<Position>,
+    video_width: u32,
+    video_height: u32,
+}
+
+// how far apart two showings of the same bitmap can be and still merge into one cue
+const MAX_MERGE_GAP_US: u64 = 42_000;
+
+// what a job sends back: the frame number of the screen and its text, if it has any
+type JobResult = (u32, Result<Option<ScreenText>, OcrError>);
+
+// state shared with the OCR jobs on the thread pool
+#[derive(Clone)]
+struct Shared {
+    // one channel per convert() call, so the pool can run the jobs of other conversions too
+    results: Sender<JobResult>,
+    review: Arc<Option<ReviewOptions>>,
+    glyphs: Option<Arc<RwLock<GlyphCache>>>,
+    disk_cache: Option<Arc<DiskCache>>,
+    language: Arc<String>,
+    verbosity: i8,
+}
+
+impl Converter {
+    /// The text of every screen of the stream, in order. Screens OCR finds no text on are left
+    /// out. When any screen's OCR fails the conversion fails with [`Error::Ocr`], listing every
+    /// screen that failed.
+    pub fn convert<R: Read>(&self, mut reader: R) -> Result<Vec<ScreenText>, Error> {
+        let mut pgs_buf = Vec::new();
+        reader.read_to_end(&mut pgs_buf)?;
+
+        if let Some(review) = &self.review {
+            fs::create_dir_all(&review.image_dir)?;
+        }
+
+        let glyphs = match &self.recognition {
+            Recognition::Tesseract => None,
+            Recognition::Glyphs { table: Some(path) } if path.exists() => {
+                Some(GlyphCache::load(path)?)
+            }
+            Recognition::Glyphs { .. } => Some(GlyphCache::new()),
+        };
+
+        let disk_cache = match &self.cache_dir {
+            Some(dir) => {
+                // glyph recognition reads differently from plain tesseract, so it gets its own keys
+                let mut config = ocr::engine_config(&self.language);
+                if glyphs.is_some() {
+                    config.push_str(" glyphs");
+                }
+
+                Some(Arc::new(DiskCache::new(dir.clone(), &config)?))
+            }
+            None => None,
+        };
+
+        let (sender, results) = mpsc::channel();
+        let shared = Shared {
+            results: sender,
+            review: Arc::new(self.review.clone()),
+            glyphs: glyphs.map(|cache| Arc::new(RwLock::new(cache))),
+            disk_cache,
+            language: Arc::new(self.language.clone()),
+            verbosity: self.verbosity,
+        };
+
+        let mut packet_handler = PacketHandler::new().with_snap(self.snap_frames);
+        let mut repeats = Repeats::default();
+        let parsed = packets(&pgs_buf)
+            .enumerate()
+            .try_for_each(|(frame_number, packet)| {
+                handle_packet(
+                    &mut packet_handler,
+                    packet?,
+                    &shared,
+                    &mut repeats,
+                    &self.pool,
+                    frame_number as u32,
+                )
+            });
+        // every queued job holds a sender, the results run out once the last one is done
+        let glyphs = shared.glyphs.clone();
+        drop(shared);
+        let mut texts = BTreeMap::new();
+        let mut failed = Vec::new();
+        let mut finished = HashSet::new();
+        for (frame_number, result) in results {
+            finished.insert(frame_number);
+            match result {
+                Ok(Some(text)) => {
+                    texts.insert(frame_number, text);
+                }
+                Ok(None) => {}
+                Err(error) => failed.push((frame_number + 1, error)),
+            }
+        }
+        parsed?;
+
+        if let (Recognition::Glyphs { table: Some(path) }, Some(glyphs)) =
+            (&self.recognition, &glyphs)
+        {
+            let image_dir = path.with_extension("glyphs");
+            match glyphs.read().unwrap().save(path, Some(&image_dir)) {
+                Err(error) if self.verbosity >= 0 => {
+                    eprintln!("error saving glyph table {} -> {}", path.display(), error)
+                }
+                _ => {}
+            }
+        }
+
+        // a job that panicked never sent its result
+        for frame_number in repeats.seen.values() {
+            if !finished.contains(frame_number) {
+                let error = OcrError::Tesseract("OCR stopped with a panic".to_string());
+                failed.push((frame_number + 1, error));
+            }
+        }
+        if !failed.is_empty() {
+            failed.sort_by_key(|(number, _)| *number);
+            return Err(Error::Ocr(failed));
+        }
+
+        for repeat in repeats.repeats {
+            if let Some(text) = texts.get(&repeat.first) {
+                let text = ScreenText {
+                    number: repeat.frame_number + 1,
+                    begin_us: repeat.begin_us,
+                    end_us: repeat.end_us,
+                    position: repeat.position,
+                    windows: repeat.windows,
+                    video_width: repeat.video_width,
+                    video_height: repeat.video_height,
+                    ..text.clone()
+                };
+                texts.insert(repeat.frame_number, text);
+            }
+        }
+
+        let screens: Vec<ScreenText> = texts.into_values().collect();
+        if self.merge_repeated {
+            Ok(merge_repeated(screens))
+        } else {
+            Ok(screens)
+        }
+    }
+}
+
+// the same bitmap shown again in the same place straight after merges into one screen
+fn merge_repeated(screens: Vec<ScreenText>) -> Vec<ScreenText> {
+    let mut out: Vec<ScreenText> = Vec::with_capacity(screens.len());
+    for screen in screens {
+        match out.last_mut() {
+            Some(prev)
+                if prev.hash == screen.hash
+                    && prev.position == screen.position
+                    && screen.begin_us <= prev.end_us + MAX_MERGE_GAP_US =>
+            {
+                prev.end_us = max(prev.end_us, screen.end_us);
+            }
+            _ => out.push(screen),
+        }
+    }
+
+    out
+}
+
+fn handle_packet(
+    packet_handler: &mut PacketHandler,
+    packet: Packet,
+    shared: &Shared,
+    repeats: &mut Repeats,
+    pool: &ThreadPool,
+    frame_number: u32,
+) -> Result<(), Error> {
+    if let Some(img) = packet_handler.handle(packet)? {
+        if let Some(first) = repeats.seen.get(&img.hash) {
+            let (position, windows) = placement(&img);
+            repeats.repeats.push(Repeat {
+                frame_number,
+                first: *first,
+                begin_us: img.begin_us,
+                end_us: img.begin_us + img.dur_us,
+                position,
+                windows,
+                video_width: img.video_width,
+                video_height: img.video_height,
+            });
+            return Ok(());
+        }
+        repeats.seen.insert(img.hash, frame_number);
+
+        let shared = shared.clone();
+        pool.execute(move || {
+            let text = get_text_from_screen(frame_number, &img, &shared);
+            if let Ok(Some(text)) = &text {
+                if shared.verbosity >= 2 {
+                    let lines: Vec<String> = text.lines.iter().map(|l| l.text()).collect();
+                    eprintln!("{}: {}", text.number, lines.join(" / "));
+                }
+            }
+            // convert() only stops listening once every job is done
+            let _ = shared.results.send((frame_number, text));
+        });
+    }
+
+    Ok(())
+}
+
+fn get_text_from_screen(
+    frame_num: u32,
+    screen: &Screen,
+    shared: &Shared,
+) -> Result<Option<ScreenText>, OcrError> {
+    let cached = shared
+        .disk_cache
+        .as_ref()
+        .and_then(|cache| cache.get(screen));
+    let lines = match cached {
+        Some(lines) => lines,
+        None => {
+            let lines = match &shared.glyphs {
+                Some(glyphs) => {
+                    glyph::recognize_screen_with_glyphs(screen, glyphs, &shared.language)?
+                }
+                None => ocr::recognize_screen(screen, &shared.language)?,
+            };
+
+            if let Some(cache) = &shared.disk_cache {
+                cache.put(screen, &lines)?;
+            }
+            lines
+        }
+    };
+
+    if lines.is_empty() {
+        return Ok(None);
+    }
+
+    let (position, windows) = placement(screen);
+    let text = ScreenText {
+        number: frame_num + 1,
+        begin_us: screen.begin_us,
+        end_us: screen.begin_us + screen.dur_us,
+        hash: screen.hash,
+        position,
+        windows,
+        video_width: screen.video_width,
+        video_height: screen.video_height,
+        colors: subtitle::dominant_colors(&screen.image),
+        lines,
+    };
+
+    if let Some(review) = shared.review.as_ref() {
+        if review.needs_review(&text) {
+            screen.image.save(review.image_path(&text))?;
+        }
+    }
+
+    Ok(Some(text))
+}
+
+// where the screen's image and windows are on the video frame
+fn placement(screen: &Screen) -> (Position, Vec<Position>) {
+    let position = Position {
+        x: screen.x,
+        y: screen.y,
+        width: screen.image.width(),
+        height: screen.image.height(),
+    };
+    let windows = screen
+        .windows
+        .iter()
+        .map(|win| Position {
+            x: win.x as u32,
+            y: win.y as u32,
+            width: win.width as u32,
+            height: win.height as u32,
+        })
+        .collect();
+
+    (position, windows)
+}
@@ -0,0 +1,156 @@
+use std::io::Cursor;
+
+use image::codecs::tiff::TiffEncoder;
+use image::{ColorType, GenericImage, RgbaImage};
+use leptess::capi;
+use leptess::tesseract::TessApi;
+
+use crate::ocr::italic::{self, Bounds};
+use crate::ocr::{OcrError, OcrLine, OcrWord};
+use crate::parser::renderer::Screen;
+
+const PAGE_SEG_MODE: capi::TessPageSegMode = capi::TessPageSegMode_PSM_SPARSE_TEXT_OSD;
+const SOURCE_RESOLUTION: i32 = 120;
+
+/// Everything that changes what tesseract reads from a given image.
+pub fn engine_config(language: &str) -> String {
+    let version = unsafe { std::ffi::CStr::from_ptr(capi::TessVersion()) };
+    format!(
+        "tesseract {} lang={} psm={} dpi={}",
+        version.to_string_lossy(),
+        language,
+        PAGE_SEG_MODE,
+        SOURCE_RESOLUTION
+    )
+}
+
+// tesseract reads text touching the image edges badly, so screens get a margin before OCR
+const PADDING_PERCENT_X: f32 = 0.12;
+const PADDING_PERCENT_Y: f32 = 0.03;
+
+/// Language is a tesseract language, eng or chi_sim, or several joined by + (eng+fra).
+pub fn recognize_screen(screen: &Screen, language: &str) -> Result<Vec<OcrLine>, OcrError> {
+    let padding_x = (screen.video_width as f32 * PADDING_PERCENT_X) as u32;
+    let padding_y = (screen.video_height as f32 * PADDING_PERCENT_Y) as u32;
+    let mut padded = RgbaImage::new(
+        screen.image.width() + 2 * padding_x,
+        screen.image.height() + 2 * padding_y,
+    );
+    padded.copy_from(&screen.image, padding_x, padding_y)?;
+
+    // handed over in memory, conversions running side by side never share a file
+    let mut tiff = Cursor::new(Vec::new());
+    TiffEncoder::new(&mut tiff).encode(
+        &padded,
+        padded.width(),
+        padded.height(),
+        ColorType::Rgba8,
+    )?;
+    let pix = leptess::leptonica::pix_read_mem(tiff.get_ref()).map_err(|error| {
+        OcrError::Tesseract(format!("leptonica can't read the screen, {:?}", error))
+    })?;
+
+    // TODO: thread local storage would probably be beneficial here to avoid recreating the API
+    // object for every screen
+    let mut tesseract_api = TessApi::new(None, language).map_err(|error| {
+        OcrError::Tesseract(format!(
+            "tesseract can't start with language {}, {:?}",
+            language, error
+        ))
+    })?;
+    let ptr = unsafe { std::ptr::read(&tesseract_api as *const _ as *const usize) as *mut _ };
+    unsafe {
+        capi::TessBaseAPISetPageSegMode(ptr, PAGE_SEG_MODE);
+    };
+    tesseract_api.set_image(&pix);
+    unsafe {
+        capi::TessBaseAPISetSourceResolution(ptr, SOURCE_RESOLUTION);
+    }
+
+    // the TSV output carries the word boxes needed to look for italics in the rendered image
+    let tsv = tesseract_api.get_tsv_text(0).map_err(|error| {
+        OcrError::Tesseract(format!("tesseract's output isn't UTF-8, {}", error))
+    })?;
+    let mut lines = lines_from_tsv(&tsv, &padded);
+    for word in lines.iter_mut().flat_map(|line| line.words.iter_mut()) {
+        word.bounds.left = word.bounds.left.saturating_sub(padding_x);
+        word.bounds.top = word.bounds.top.saturating_sub(padding_y);
+    }
+
+    Ok(lines)
+}
+
+struct TsvWord {
+    line_key: (u32, u32, u32),
+    bounds: Bounds,
+    confidence: f32,
+    text: String,
+}
+
+// columns: level, page, block, paragraph, line, word, left, top, width, height, conf, text
+fn parse_tsv_word(row: &str) -> Option<TsvWord> {
+    let cols: Vec<&str> = row.splitn(12, '\t').collect();
+    if cols.len() != 12 || cols[0] != "5" {
+        return None;
+    }
+
+    let num = |idx: usize| cols[idx].trim().parse::<u32>().ok();
+    let text = cols[11].trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    Some(TsvWord {
+        line_key: (num(2)?, num(3)?, num(4)?),
+        bounds: Bounds {
+            left: num(6)?,
+            top: num(7)?,
+            width: num(8)?,
+            height: num(9)?,
+        },
+        confidence: cols[10].trim().parse::<f32>().ok()?.max(0.0),
+        text: text.to_string(),
+    })
+}
+
+fn lines_from_tsv(tsv: &str, image: &RgbaImage) -> Vec<OcrLine> {
+    let mut grouped: Vec<Vec<TsvWord>> = Vec::new();
+    for word in tsv.lines().filter_map(parse_tsv_word) {
+        match grouped.last_mut() {
+            Some(line) if line[0].line_key == word.line_key => line.push(word),
+            _ => grouped.push(vec![word]),
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|words| {
+            let line_bounds = words[1..]
+                .iter()
+                .fold(words[0].bounds, |acc, word| acc.union(&word.bounds));
+            let line_italic = italic::is_italic(image, line_bounds).unwrap_or(false);
+
+            OcrLine {
+                words: words
+                    .into_iter()
+                    .map(|word| {
+                        // words narrower than they are tall don't have enough strokes to
+                        // measure reliably, so they follow the rest of their line
+                        let italic = if word.bounds.width < word.bounds.height {
+                            line_italic
+                        } else {
+                            italic::is_italic(image, word.bounds).unwrap_or(line_italic)
+                        };
+
+                        OcrWord {
+                            text: word.text,
+                            italic,
+                            confidence: word.confidence,
+                            bounds: word.bounds,
+                        }
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
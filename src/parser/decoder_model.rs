@@ -0,0 +1,268 @@
+use std::cmp::max;
+use std::collections::HashMap;
+
+use crate::parser::display_set::display_sets;
+use crate::parser::parse::packets_at;
+use crate::parser::timestamp::CLOCK_RATE;
+use crate::parser::types::{Segment, Timestamp};
+use crate::parser::validate::{Rule, Violation};
+use crate::parser::write::write_rle;
+
+// the HDMV PG decoder model: objects are decoded from the coded data buffer into the decoded
+// object buffer at RD, the graphics plane is cleared and windows drawn at RC (bits per second,
+// one byte per pixel)
+pub(crate) const RD: u64 = 128_000_000;
+pub(crate) const RC: u64 = 256_000_000;
+const CODED_DATA_BUFFER: usize = 1024 * 1024;
+const DECODED_OBJECT_BUFFER: usize = 4 * 1024 * 1024;
+
+// ticks it takes to move this many pixels at a rate
+pub(crate) fn transfer_time(pixels: usize, rate: u64) -> Timestamp {
+    let bits = pixels as u128 * 8 * CLOCK_RATE as u128;
+    Timestamp::from_ticks(bits.div_ceil(rate as u128) as u64)
+}
+
+/// Display sets a player following the decoder model can't decode or draw by their PTS, and
+/// buffers they overflow. A set starts decoding at its DTS, but not before the set ahead of it was
+/// presented, and needs the plane cleared (at an epoch start), its objects decoded and its windows
+/// drawn one after the other, the model's conservative form.
+pub fn check_decoder_model(pgs_buf: &[u8]) -> Vec<Violation> {
+    let parsed = packets_at(pgs_buf)
+        .map_while(Result::ok)
+        .collect::<Vec<_>>();
+
+    let mut violations = Vec::new();
+    // object id -> decoded size, and window id -> size, for the epoch
+    let mut objects: HashMap<u16, usize> = HashMap::new();
+    let mut windows: HashMap<u8, usize> = HashMap::new();
+    let mut last_pts = Timestamp::default();
+    let mut presented = None;
+    for set in display_sets(parsed) {
+        let pcs = match set.composition() {
+            Some(pcs) => pcs,
+            // validate reports these, there's no PTS to decode them by
+            None => continue,
+        };
+        if set.starts_epoch() {
+            objects.clear();
+            windows.clear();
+        }
+
+        let (offset, first) = &set.packets[0];
+        let pts = first.pts.after(last_pts);
+        let dts = first.dts.after(last_pts);
+
+        let mut needed = if set.starts_epoch() {
+            transfer_time(pcs.width as usize * pcs.height as usize, RC)
+        } else {
+            Timestamp::default()
+        };
+        let mut coded = 0;
+        // object id -> (decoded size, offset, DTS) of the first segment of a definition split
+        // over several
+        let mut fragments: HashMap<u16, (usize, usize, Timestamp)> = HashMap::new();
+        for (offset, packet) in &set.packets {
+            last_pts = packet.pts.after(last_pts);
+            match &packet.segment {
+                Segment::WindowDefinition(defined) => {
+                    for window in defined {
+                        windows.insert(window.id, window.width as usize * window.height as usize);
+                    }
+                }
+                Segment::ObjectDefinition(ods) => {
+                    let mut data = Vec::new();
+                    write_rle(&mut data, &ods.data_raw);
+                    coded += data.len();
+
+                    let (size, first_offset, object_dts) = match fragments.remove(&ods.id) {
+                        Some(fragment) if !ods.is_first_in_sequence => fragment,
+                        _ => (
+                            ods.width as usize * ods.height as usize,
+                            *offset,
+                            packet.dts.after(pts),
+                        ),
+                    };
+                    if !ods.is_last_in_sequence {
+                        fragments.insert(ods.id, (size, first_offset, object_dts));
+                        continue;
+                    }
+
+                    // the object is decoded between the DTS of its first segment and the PTS of
+                    // its last
+                    let decode = transfer_time(size, RD);
+                    let available = packet.pts.after(pts) - object_dts;
+                    if available < decode {
+                        violations.push(Violation {
+                            offset: first_offset,
+                            rule: Rule::ObjectDecodeTooSlow {
+                                id: ods.id,
+                                needed: decode,
+                                available,
+                            },
+                        });
+                    }
+                    needed = needed + decode;
+                    objects.insert(ods.id, size);
+                }
+                _ => {}
+            }
+        }
+        needed = needed + transfer_time(windows.values().sum(), RC);
+
+        if coded > CODED_DATA_BUFFER {
+            violations.push(Violation {
+                offset: *offset,
+                rule: Rule::CodedDataBufferFull { bytes: coded },
+            });
+        }
+        let decoded: usize = objects.values().sum();
+        if decoded > DECODED_OBJECT_BUFFER {
+            violations.push(Violation {
+                offset: *offset,
+                rule: Rule::DecodedObjectBufferFull { bytes: decoded },
+            });
+        }
+
+        let start = match presented {
+            Some(presented) => max(dts, presented),
+            None => dts,
+        };
+        let available = pts - start;
+        if available < needed {
+            violations.push(Violation {
+                offset: *offset,
+                rule: Rule::CompositionTooLate { needed, available },
+            });
+        }
+        presented = Some(pts);
+    }
+
+    violations.sort_by_key(|violation| violation.offset);
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::{
+        CompositionState, ObjectDefinition, Packet, PresentationComposition, RLEEntry,
+        WindowDefinition,
+    };
+    use crate::parser::write::write_packet;
+
+    // (dts, pts) in ms
+    fn stream(segments: Vec<((u64, u64), Segment)>) -> Vec<u8> {
+        let mut out = Vec::new();
+        for ((dts, pts), segment) in segments {
+            let packet = Packet {
+                pts: Timestamp::from_millis(pts),
+                dts: Timestamp::from_millis(dts),
+                segment,
+            };
+            write_packet(&mut out, &packet);
+        }
+        out
+    }
+
+    fn pcs(state: CompositionState) -> Segment {
+        Segment::PresentationComposition(PresentationComposition {
+            width: 1920,
+            height: 1080,
+            frame_rate: 0x10,
+            number: 0,
+            state,
+            palette_update: false,
+            palette_id: 0,
+            objects: vec![],
+        })
+    }
+
+    fn wds() -> Segment {
+        Segment::WindowDefinition(vec![WindowDefinition {
+            id: 0,
+            x: 0,
+            y: 800,
+            width: 1920,
+            height: 200,
+        }])
+    }
+
+    fn ods(width: u16, height: u16) -> Segment {
+        Segment::ObjectDefinition(ObjectDefinition {
+            id: 0,
+            version: 0,
+            is_last_in_sequence: true,
+            is_first_in_sequence: true,
+            width,
+            height,
+            data_raw: vec![RLEEntry::EndOfLine],
+        })
+    }
+
+    #[test]
+    fn test_transfer_time() {
+        // clearing a 1080p plane takes 64.8ms
+        assert_eq!(transfer_time(1920 * 1080, RC), Timestamp::from_ticks(5832));
+        assert_eq!(transfer_time(1, RD), Timestamp::from_ticks(1));
+    }
+
+    #[test]
+    fn test_in_time() {
+        let buf = stream(vec![
+            ((850, 1_000), pcs(CompositionState::EpochStart)),
+            ((850, 1_000), wds()),
+            ((850, 950), ods(1920, 200)),
+            ((0, 1_000), Segment::End),
+            ((1_900, 2_000), pcs(CompositionState::Normal)),
+            ((0, 2_000), Segment::End),
+        ]);
+        assert_eq!(check_decoder_model(&buf), vec![]);
+    }
+
+    #[test]
+    fn test_too_late() {
+        let buf = stream(vec![
+            // 64.8ms to clear the plane, 24ms to decode the object and 12ms to draw the window
+            ((950, 1_000), pcs(CompositionState::EpochStart)),
+            ((950, 1_000), wds()),
+            ((950, 960), ods(1920, 200)),
+            ((0, 1_000), Segment::End),
+            // can't start before the set ahead of it shows
+            ((900, 1_005), pcs(CompositionState::Normal)),
+            ((0, 1_005), Segment::End),
+        ]);
+
+        let rules: Vec<(usize, Rule)> = check_decoder_model(&buf)
+            .into_iter()
+            .map(|violation| (violation.offset, violation.rule))
+            .collect();
+        let ms = Timestamp::from_millis;
+        assert_eq!(
+            rules,
+            vec![
+                (
+                    0,
+                    Rule::CompositionTooLate {
+                        needed: Timestamp::from_ticks(5832 + 2160 + 1080),
+                        available: ms(50),
+                    }
+                ),
+                (
+                    47,
+                    Rule::ObjectDecodeTooSlow {
+                        id: 0,
+                        needed: Timestamp::from_ticks(2160),
+                        available: ms(10),
+                    }
+                ),
+                (
+                    86,
+                    Rule::CompositionTooLate {
+                        needed: Timestamp::from_ticks(1080),
+                        available: ms(5),
+                    }
+                ),
+            ]
+        );
+    }
+}
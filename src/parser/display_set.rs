@@ -0,0 +1,148 @@
+use crate::parser::types::{CompositionState, Packet, PresentationComposition, Segment};
+
+/// The packets of one display set, a presentation composition up to the END closing it, each with
+/// the byte offset it starts at.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DisplaySet {
+    pub packets: Vec<(usize, Packet)>,
+}
+
+impl DisplaySet {
+    /// None for segments found outside any display set.
+    pub fn composition(&self) -> Option<&PresentationComposition> {
+        match self.packets.first() {
+            Some((_, packet)) => match &packet.segment {
+                Segment::PresentationComposition(pcs) => Some(pcs),
+                _ => None,
+            },
+            None => None,
+        }
+    }
+
+    /// The composition is an epoch start.
+    pub fn starts_epoch(&self) -> bool {
+        matches!(
+            self.composition(),
+            Some(PresentationComposition {
+                state: CompositionState::EpochStart,
+                ..
+            })
+        )
+    }
+
+    /// Closed by an END segment.
+    pub fn is_complete(&self) -> bool {
+        matches!(
+            self.packets.last(),
+            Some((
+                _,
+                Packet {
+                    segment: Segment::End,
+                    ..
+                }
+            ))
+        )
+    }
+
+    /// The byte offset of the first packet.
+    pub fn offset(&self) -> usize {
+        self.packets.first().map_or(0, |(offset, _)| *offset)
+    }
+}
+
+/// Packets grouped as a decoder sees them, a presentation composition starts a display set and an
+/// END closes it. A set missing its END is closed by the next composition, segments after an END
+/// and before the next composition make up a set of their own.
+pub fn display_sets(packets: impl IntoIterator<Item = (usize, Packet)>) -> Vec<DisplaySet> {
+    let mut sets = Vec::new();
+    let mut current = DisplaySet::default();
+    for (offset, packet) in packets {
+        let is_composition = matches!(packet.segment, Segment::PresentationComposition(_));
+        if is_composition && !current.packets.is_empty() {
+            sets.push(std::mem::take(&mut current));
+        }
+
+        let is_end = packet.segment == Segment::End;
+        current.packets.push((offset, packet));
+        if is_end {
+            sets.push(std::mem::take(&mut current));
+        }
+    }
+
+    if !current.packets.is_empty() {
+        sets.push(current);
+    }
+    sets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::Timestamp;
+
+    fn packet(segment: Segment) -> Packet {
+        Packet {
+            pts: Timestamp::default(),
+            dts: Timestamp::default(),
+            segment,
+        }
+    }
+
+    fn pcs(state: CompositionState) -> Segment {
+        Segment::PresentationComposition(PresentationComposition {
+            width: 1920,
+            height: 1080,
+            frame_rate: 0x10,
+            number: 0,
+            state,
+            palette_update: false,
+            palette_id: 0,
+            objects: vec![],
+        })
+    }
+
+    #[test]
+    fn test_display_sets() {
+        let segments = vec![
+            pcs(CompositionState::EpochStart),
+            Segment::WindowDefinition(vec![]),
+            Segment::End,
+            // missing its END
+            pcs(CompositionState::Normal),
+            pcs(CompositionState::AcquisitionPoint),
+            Segment::End,
+            // orphaned
+            Segment::WindowDefinition(vec![]),
+            Segment::End,
+        ];
+        let sets = display_sets(
+            segments
+                .into_iter()
+                .enumerate()
+                .map(|(idx, segment)| (idx * 13, packet(segment))),
+        );
+
+        let shape: Vec<(usize, usize, bool, bool)> = sets
+            .iter()
+            .map(|set| {
+                (
+                    set.offset(),
+                    set.packets.len(),
+                    set.composition().is_some(),
+                    set.is_complete(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            shape,
+            vec![
+                (0, 3, true, true),
+                (39, 1, true, false),
+                (52, 2, true, true),
+                (78, 2, false, true),
+            ]
+        );
+        assert!(sets[0].starts_epoch());
+        assert!(!sets[2].starts_epoch());
+    }
+}
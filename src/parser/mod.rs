@@ -1,3 +1,22 @@
+/// The buffer and timing model of a PGS decoder.
+pub mod decoder_model;
+/// Packets grouped into display sets.
+pub mod display_set;
+/// Segments and packets read from .sup bytes.
 pub mod parse;
+/// Display sets drawn into the images shown on screen.
 pub mod renderer;
+/// Fixes for streams that break the PGS rules.
+pub mod repair;
+#[cfg(feature = "serde")]
+mod serde_rle;
+/// Cutting and joining streams.
+pub mod splice;
+/// The 90kHz clock and video frame rates.
+pub mod timestamp;
+/// The segments of a PGS stream.
 pub mod types;
+/// Checks against the PGS rules.
+pub mod validate;
+/// Packets written back as .sup bytes.
+pub mod write;
@@ -1,12 +1,13 @@
 extern crate nom;
 
 use crate::parser::types::*;
+use crate::Error;
 
 use self::nom::{
     branch::alt,
     bytes::complete::tag,
     combinator::{flat_map, map, value},
-    error::{context, ErrorKind, ParseError},
+    error::{context, ErrorKind, ParseError, VerboseError},
     multi::{count, many1},
     number::complete::{be_u16, be_u24, be_u32, be_u8},
     sequence::{preceded, tuple},
@@ -46,7 +47,10 @@ fn seg_pds<'a, E: ParseError<&'a [u8]>>(
         tuple((
             context("id", be_u8),
             context("version", be_u8),
-            context("entries", count(seg_pds_entry, usize::from((size - 2) / 5))),
+            context(
+                "entries",
+                count(seg_pds_entry, usize::from(size.saturating_sub(2) / 5)),
+            ),
         )),
         |(id, version, entries)| {
             Segment::PaletteDefinition(PaletteDefinition {
@@ -87,8 +91,13 @@ fn seg_ods<'a, E: ParseError<&'a [u8]>>(
             context("height", be_u16),
         ))(i)?;
 
-        // - 4 because data_size includes width & height which is 2 * 2 bytes
-        let (rest, data_raw) = after_info.take_split((data_size - 4) as usize);
+        // - 4 because data_size includes width & height which is 2 * 2 bytes. A damaged size
+        // fails the packet rather than reading past the end
+        let data_len = match (data_size as usize).checked_sub(4) {
+            Some(len) if len <= after_info.len() => len,
+            _ => return Err(nom::Err::Error(nom::error::make_error(i, ErrorKind::Eof))),
+        };
+        let (rest, data_raw) = after_info.take_split(data_len);
         let (_, rle_data) = rle_data(data_raw)?;
 
         Ok((
@@ -111,6 +120,18 @@ fn rle_data<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Vec<R
     many1(rle_entry)(i)
 }
 
+/// The RLE data of an object on its own, as ODS `data_raw` holds it.
+pub fn parse_rle(data: &[u8]) -> Option<RLEData> {
+    if data.is_empty() {
+        return Some(Vec::new());
+    }
+
+    match rle_data::<VerboseError<&[u8]>>(data) {
+        Ok(([], entries)) => Some(entries),
+        _ => None,
+    }
+}
+
 fn rle_entry<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], RLEEntry, E> {
     if i.is_empty() {
         return Err(nom::Err::Error(nom::error::make_error(i, ErrorKind::Eof)));
@@ -145,7 +166,10 @@ fn rle_entry<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], RLEE
         0
     } else {
         l_consumed += 1;
-        i[l_consumed]
+        match i.get(l_consumed) {
+            Some(col) => *col,
+            None => return Err(nom::Err::Error(nom::error::make_error(i, ErrorKind::Eof))),
+        }
     };
 
     let rest = &i[(1 + l_consumed)..];
@@ -177,10 +201,11 @@ fn seg_pcs<'a, E: ParseError<&'a [u8]>>(
                 )
             }),
         )),
-        |(w, h, _, cn, s, u, pid, objs)| {
+        |(w, h, fr, cn, s, u, pid, objs)| {
             Segment::PresentationComposition(PresentationComposition {
                 width: w,
                 height: h,
+                frame_rate: fr,
                 number: cn,
                 state: s,
                 palette_update: u,
@@ -250,7 +275,10 @@ fn seg_wds<'a, E: ParseError<&'a [u8]>>(
             context("num_windows", be_u8),
             context(
                 "windows",
-                count(context("def", seg_wds_win), usize::from((size - 1) / 9)),
+                count(
+                    context("def", seg_wds_win),
+                    usize::from(size.saturating_sub(1) / 9),
+                ),
             ),
         ),
         Segment::WindowDefinition,
@@ -276,6 +304,7 @@ fn seg_wds_win<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Wi
     )(i)
 }
 
+/// One packet, the PG magic, PTS, DTS and a segment.
 pub fn get_packet<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Packet, E> {
     context(
         "packet",
@@ -292,3 +321,62 @@ pub fn get_packet<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8],
         ),
     )(i)
 }
+
+/// The packets of a stream in order, stopping at the first that can't be read.
+pub fn packets(pgs_buf: &[u8]) -> impl Iterator<Item = Result<Packet, Error>> + '_ {
+    packets_at(pgs_buf).map(|packet| packet.map(|(_, packet)| packet))
+}
+
+/// `packets()` with the byte offset each packet starts at.
+pub fn packets_at(pgs_buf: &[u8]) -> impl Iterator<Item = Result<(usize, Packet), Error>> + '_ {
+    let mut rest = pgs_buf;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let offset = pgs_buf.len() - rest.len();
+        match get_packet::<VerboseError<&[u8]>>(rest) {
+            Ok((remains, packet)) => {
+                rest = remains;
+                Some(Ok((offset, packet)))
+            }
+            Err(_) => {
+                rest = &[];
+                Some(Err(Error::Parse { offset }))
+            }
+        }
+    })
+}
+
+/// `packets_at()` that carries on after a packet that can't be read: the error is yielded, then
+/// reading starts again at the next "PG" a packet can be read from.
+pub fn packets_recovering(
+    pgs_buf: &[u8],
+) -> impl Iterator<Item = Result<(usize, Packet), Error>> + '_ {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        if offset >= pgs_buf.len() {
+            return None;
+        }
+
+        let rest = &pgs_buf[offset..];
+        match get_packet::<VerboseError<&[u8]>>(rest) {
+            Ok((remains, packet)) => {
+                let at = offset;
+                offset = pgs_buf.len() - remains.len();
+                Some(Ok((at, packet)))
+            }
+            Err(_) => {
+                let at = offset;
+                offset = (at + 1..pgs_buf.len())
+                    .find(|&idx| {
+                        pgs_buf[idx..].starts_with(b"PG")
+                            && get_packet::<VerboseError<&[u8]>>(&pgs_buf[idx..]).is_ok()
+                    })
+                    .unwrap_or(pgs_buf.len());
+                Some(Err(Error::Parse { offset: at }))
+            }
+        }
+    })
+}
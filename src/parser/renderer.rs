@@ -1,26 +1,47 @@
 use std::cmp::{max, min};
+use std::hash::Hasher;
 
 use image::{ImageBuffer, Rgba, RgbaImage};
 use nom::lib::std::collections::HashMap;
 
+use crate::hash::StableHasher;
 use crate::parser::types::{
-    CompositionObject, ObjectDefinition, Packet, PresentationComposition, RLEEntry, Segment,
-    Timestamp, WindowDefinition, YCrCbAColor,
+    CompositionObject, FrameRate, ObjectDefinition, Packet, PresentationComposition, RLEEntry,
+    Segment, Timestamp, WindowDefinition, YCrCbAColor,
 };
 
+/// An image shown on screen, everything the compositions draw between two display sets.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Screen {
     pub image: RgbaImage,
-    // microsecond offset for when to show this image
+    /// Microsecond offset for when to show this image.
     pub begin_us: u64,
-    // microsecond duration for how long to show this image
+    /// Microsecond duration for how long to show this image.
     pub dur_us: u64,
 
+    /// Where the image sits on a video frame of `video_width` x `video_height`.
     pub x: u32,
     pub y: u32,
+    pub video_width: u32,
+    pub video_height: u32,
+    /// Windows the composition objects are drawn in, in video frame coordinates.
+    pub windows: Vec<WindowDefinition>,
+
+    /// Hash of the decoded indexed bitmap and palette, equal for screens that look the same.
+    pub hash: u64,
+}
+
+/// Cue times moved to the nearest video frame boundary.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FrameSnap {
+    /// The rate in the presentation composition, times are left alone when it's not one PGS
+    /// defines.
+    Stream,
+    Rate(FrameRate),
 }
 
-#[derive(Derivative)]
+/// Draws packets fed in stream order into screens.
+#[derive(Derivative, Default)]
 #[derivative(Debug)]
 pub struct PacketHandler {
     composition: Option<PresentationComposition>,
@@ -31,50 +52,41 @@ pub struct PacketHandler {
     object_data: HashMap<u16, ObjectDefinition>,
     begin_at: Option<Timestamp>,
     end_at: Option<Timestamp>,
+    // the last PTS seen, on the unwrapped timeline
+    last_pts: Option<Timestamp>,
+    snap: Option<FrameSnap>,
 }
 
+/// Why a packet couldn't be drawn.
 #[derive(Debug, PartialEq, Clone)]
 pub enum HandleError {
+    /// The object's RLE data doesn't decode to its width x height.
     BadObjectDefinition,
 }
 
 impl PacketHandler {
+    /// A handler that leaves times where the stream has them.
     pub fn new() -> PacketHandler {
-        PacketHandler {
-            composition: None,
-            comp_objects: HashMap::new(),
-            palette_entries: HashMap::new(),
-            windows: HashMap::new(),
-            object_data: HashMap::new(),
-            begin_at: None,
-            end_at: None,
-        }
+        PacketHandler::default()
     }
 
+    /// Cue times moved to frame boundaries.
+    pub fn with_snap(self, snap: Option<FrameSnap>) -> PacketHandler {
+        PacketHandler { snap, ..self }
+    }
+
+    /// A screen once a composition with no objects clears what the ones before it drew.
     pub fn handle(&mut self, packet: Packet) -> Result<Option<Screen>, HandleError> {
+        let pts = match self.last_pts {
+            Some(last) => packet.pts.after(last),
+            None => packet.pts,
+        };
+        self.last_pts = Some(pts);
+
         match packet.segment {
             Segment::PresentationComposition(pcs) => {
-                self.begin_at = match self.begin_at {
-                    Some(v) => {
-                        if packet.pts < v {
-                            Some(packet.pts)
-                        } else {
-                            Some(v)
-                        }
-                    }
-                    None => Some(packet.pts),
-                };
-
-                self.end_at = match self.end_at {
-                    Some(v) => {
-                        if packet.pts > v {
-                            Some(packet.pts)
-                        } else {
-                            Some(v)
-                        }
-                    }
-                    None => Some(packet.pts),
-                };
+                self.begin_at = Some(self.begin_at.map_or(pts, |v| min(v, pts)));
+                self.end_at = Some(self.end_at.map_or(pts, |v| max(v, pts)));
 
                 let res = if pcs.objects.is_empty() {
                     self.generate_display()
@@ -159,25 +171,21 @@ impl PacketHandler {
             img_height = max(img_height, proposed_height);
         }
 
-        const PADDING_PERCENT_X: f32 = 0.12;
-        const PADDING_PERCENT_Y: f32 = 0.03;
-        let padding_x = (pcs.width as f32 * PADDING_PERCENT_X) as u32;
-        let dx = img_x - (max(0, img_x as i32 - padding_x as i32) as u32);
-        img_width += 2 * dx;
-        img_x -= dx;
-
-        let padding_y = (pcs.height as f32 * PADDING_PERCENT_Y) as u32;
-        let dy = img_y - (max(0, img_y as i32 - padding_y as i32) as u32);
-        img_height += 2 * dy;
-        img_y -= dy;
-
         let mut img_data = ImageBuffer::<Rgba<u8>, Vec<u8>>::new(img_width, img_height);
 
         let palette = self.palette_entries.get(&pcs.palette_id)?;
+        let mut hasher = StableHasher::new();
+        for color in palette.iter() {
+            hasher.write(&color.0);
+        }
+        let mut windows: Vec<WindowDefinition> = Vec::new();
         for comp_obj in &pcs.objects {
             let id = comp_obj.id;
             let obj = self.object_data.get(&id)?;
             let window = self.windows.get(&comp_obj.window_id)?;
+            if !windows.contains(window) {
+                windows.push(window.clone());
+            }
             let x0: u32 = comp_obj.x as u32 - img_x;
             let y0: u32 = comp_obj.y as u32 - img_y;
 
@@ -193,6 +201,9 @@ impl PacketHandler {
 
             let obj_width_show: u32 = max_x - x0;
             let obj_height_show: u32 = max_y - y0;
+            hasher.write_u32(x0);
+            hasher.write_u32(y0);
+            hash_object(&mut hasher, obj);
 
             let mut x_offset: u32 = 0;
             let mut y_offset: u32 = 0;
@@ -241,14 +252,32 @@ impl PacketHandler {
             }
         }
 
-        let begin_at = pts_to_microsec(self.begin_at?);
-        let dur = pts_to_microsec(self.end_at?) - begin_at;
+        let mut begin_at = self.begin_at?;
+        let mut end_at = self.end_at?;
+        let rate = match self.snap {
+            Some(FrameSnap::Stream) => FrameRate::from_pgs(pcs.frame_rate),
+            Some(FrameSnap::Rate(rate)) => Some(rate),
+            None => None,
+        };
+        if let Some(rate) = rate {
+            begin_at = begin_at.snap(rate);
+            // a cue never snaps down to nothing, it's shown for at least a frame
+            end_at = max(
+                end_at.snap(rate),
+                begin_at + Timestamp::from_frames(1, rate),
+            );
+        }
+        let dur = end_at - begin_at;
         let dis = Screen {
             image: img_data,
-            begin_us: begin_at,
-            dur_us: dur,
+            begin_us: begin_at.as_micros(),
+            dur_us: dur.as_micros(),
             x: img_x,
             y: img_y,
+            video_width: pcs.width as u32,
+            video_height: pcs.height as u32,
+            windows,
+            hash: hasher.finish(),
         };
 
         self.reset();
@@ -299,11 +328,38 @@ fn constrain_double_to_byte(data: f64) -> u8 {
     }
 }
 
-fn pts_to_microsec(ts: Timestamp) -> u64 {
-    (ts as u64 / 9) * 100
+// hashes the decoded pixels rather than the RLE entries, two encoders can produce different runs
+// for the same bitmap
+fn hash_object<H: Hasher>(hasher: &mut H, obj: &ObjectDefinition) {
+    hasher.write_u16(obj.width);
+    hasher.write_u16(obj.height);
+
+    let mut run: Option<(u8, usize)> = None;
+    let mut push = |hasher: &mut H, color: u8, count: usize| match run {
+        Some((c, n)) if c == color => run = Some((c, n + count)),
+        Some((c, n)) => {
+            hasher.write_u8(c);
+            hasher.write_usize(n);
+            run = Some((color, count));
+        }
+        None => run = Some((color, count)),
+    };
+
+    for entry in &obj.data_raw {
+        match entry {
+            RLEEntry::Single(color) => push(hasher, *color, 1),
+            RLEEntry::Repeated { color, count } => push(hasher, *color, *count as usize),
+            RLEEntry::EndOfLine => {}
+        }
+    }
+
+    if let Some((c, n)) = run {
+        hasher.write_u8(c);
+        hasher.write_usize(n);
+    }
 }
 
-fn rle_total_count(data: &Vec<RLEEntry>) -> usize {
+pub(crate) fn rle_total_count(data: &[RLEEntry]) -> usize {
     let mut out: usize = 0;
     for entry in data {
         match entry {
@@ -0,0 +1,500 @@
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::decoder_model::{transfer_time, RC, RD};
+use crate::parser::display_set::display_sets;
+use crate::parser::parse::packets_recovering;
+use crate::parser::types::{CompositionState, Packet, Segment, Timestamp};
+use crate::parser::write::write_packet;
+use crate::Error;
+
+/// What `repair()` changed.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RepairReport {
+    /// Bytes no packet could be read from, skipped up to the next packet that could.
+    pub unreadable_bytes: usize,
+    /// Segments outside any display set, and fragments of objects missing a first or last one.
+    pub dropped_segments: usize,
+    /// Composition objects using an object, window or palette the epoch doesn't define, and
+    /// palette updates of an undefined palette.
+    pub dropped_references: usize,
+    pub ends_added: usize,
+    pub renumbered: usize,
+    /// Compositions made epoch starts, the first one and any changing the video size.
+    pub epochs_started: usize,
+    /// Segments given a new DTS or PTS.
+    pub retimed: usize,
+}
+
+impl RepairReport {
+    /// Nothing needed fixing.
+    pub fn is_empty(&self) -> bool {
+        *self == RepairReport::default()
+    }
+}
+
+/// The stream rebuilt so it follows the PGS rules. Composition PTS are kept, everything else is
+/// rewritten: display sets get their END, composition objects using anything the epoch doesn't
+/// define are dropped, composition numbers count up from 0 and each
+/// segment's DTS and PTS are worked out again from the decoder model, so a set decodes as late as
+/// it can (but not before the one ahead of it is presented).
+pub fn repair(pgs_buf: &[u8]) -> (Vec<u8>, RepairReport) {
+    let mut report = RepairReport::default();
+    let mut parsed = Vec::new();
+    let mut unreadable_at = None;
+    for packet in packets_recovering(pgs_buf) {
+        match packet {
+            Ok((offset, packet)) => {
+                if let Some(start) = unreadable_at.take() {
+                    report.unreadable_bytes += offset - start;
+                }
+                parsed.push((offset, packet));
+            }
+            Err(Error::Parse { offset }) => unreadable_at = Some(offset),
+            Err(_) => {}
+        }
+    }
+    if let Some(start) = unreadable_at {
+        report.unreadable_bytes += pgs_buf.len() - start;
+    }
+
+    let mut out = Vec::with_capacity(pgs_buf.len());
+    let mut number: u16 = 0;
+    // the video size of the epoch, and window id -> size
+    let mut frame = None;
+    let mut windows: HashMap<u8, usize> = HashMap::new();
+    let mut defined = Defined::default();
+    let mut last_pts = Timestamp::default();
+    let mut presented = None;
+    for set in display_sets(parsed) {
+        if set.composition().is_none() {
+            report.dropped_segments += set.packets.len();
+            continue;
+        }
+
+        // on the unwrapped timeline, write_packet wraps them again
+        let pts = set.packets[0].1.pts.after(last_pts);
+        for (_, packet) in &set.packets {
+            last_pts = packet.pts.after(last_pts);
+        }
+
+        let mut packets: Vec<Packet> = set.packets.into_iter().map(|(_, packet)| packet).collect();
+        if packets.last().map(|packet| &packet.segment) != Some(&Segment::End) {
+            packets.push(Packet {
+                pts,
+                dts: pts,
+                segment: Segment::End,
+            });
+            report.ends_added += 1;
+        }
+        report.dropped_segments += drop_broken_objects(&mut packets);
+
+        if let Segment::PresentationComposition(pcs) = &mut packets[0].segment {
+            if pcs.number != number {
+                pcs.number = number;
+                report.renumbered += 1;
+            }
+            number = number.wrapping_add(1);
+
+            if frame != Some((pcs.width, pcs.height)) && pcs.state != CompositionState::EpochStart {
+                pcs.state = CompositionState::EpochStart;
+                report.epochs_started += 1;
+            }
+            if pcs.state == CompositionState::EpochStart {
+                frame = Some((pcs.width, pcs.height));
+                windows.clear();
+                defined = Defined::default();
+            }
+        }
+        report.dropped_references += drop_undefined_references(&mut packets, &mut defined);
+
+        let times: Vec<(u32, u32)> = packets.iter().map(sup_times).collect();
+        retime_set(&mut packets, pts, presented, &mut windows);
+        report.retimed += packets
+            .iter()
+            .zip(times)
+            .filter(|(packet, times)| sup_times(packet) != *times)
+            .count();
+        presented = Some(pts);
+        for packet in &packets {
+            write_packet(&mut out, packet);
+        }
+    }
+
+    (out, report)
+}
+
+// the PTS and DTS as the packet stores them
+fn sup_times(packet: &Packet) -> (u32, u32) {
+    (packet.pts.sup_ticks(), packet.dts.sup_ticks())
+}
+
+// removes the fragments of objects that don't run from a first to a last fragment, returning
+// how many were removed
+fn drop_broken_objects(packets: &mut Vec<Packet>) -> usize {
+    let mut keep = vec![true; packets.len()];
+    // object id -> indices of the fragments read so far
+    let mut open: HashMap<u16, Vec<usize>> = HashMap::new();
+    for (idx, packet) in packets.iter().enumerate() {
+        if let Segment::ObjectDefinition(ods) = &packet.segment {
+            if ods.is_first_in_sequence {
+                // the object before it never got its last fragment
+                for dropped in open.remove(&ods.id).unwrap_or_default() {
+                    keep[dropped] = false;
+                }
+            }
+            match open.get_mut(&ods.id) {
+                Some(fragments) => fragments.push(idx),
+                None if ods.is_first_in_sequence => {
+                    open.insert(ods.id, vec![idx]);
+                }
+                None => keep[idx] = false,
+            }
+            if ods.is_last_in_sequence {
+                open.remove(&ods.id);
+            }
+        }
+    }
+    for dropped in open.into_values().flatten() {
+        keep[dropped] = false;
+    }
+
+    let before = packets.len();
+    let mut keep = keep.into_iter();
+    packets.retain(|_| keep.next().unwrap());
+    before - packets.len()
+}
+
+// what the segments of an epoch have defined so far
+#[derive(Default)]
+struct Defined {
+    objects: HashSet<u16>,
+    windows: HashSet<u8>,
+    palettes: HashSet<u8>,
+}
+
+// takes in the definitions of the set, then removes what its composition uses that's still
+// undefined (broken objects are gone by now), returning how many references were removed
+fn drop_undefined_references(packets: &mut [Packet], defined: &mut Defined) -> usize {
+    for packet in packets.iter() {
+        match &packet.segment {
+            Segment::WindowDefinition(windows) => {
+                defined
+                    .windows
+                    .extend(windows.iter().map(|window| window.id));
+            }
+            Segment::PaletteDefinition(pds) => {
+                defined.palettes.insert(pds.id);
+            }
+            Segment::ObjectDefinition(ods) if ods.is_last_in_sequence => {
+                defined.objects.insert(ods.id);
+            }
+            _ => {}
+        }
+    }
+
+    let pcs = match &mut packets[0].segment {
+        Segment::PresentationComposition(pcs) => pcs,
+        _ => return 0,
+    };
+    let before = pcs.objects.len();
+    if defined.palettes.contains(&pcs.palette_id) {
+        pcs.objects.retain(|object| {
+            defined.objects.contains(&object.id) && defined.windows.contains(&object.window_id)
+        });
+    } else {
+        // nothing can be drawn without the palette
+        pcs.objects.clear();
+    }
+    let mut dropped = before - pcs.objects.len();
+    if pcs.palette_update && !defined.palettes.contains(&pcs.palette_id) {
+        pcs.palette_update = false;
+        dropped += 1;
+    }
+    dropped
+}
+
+// decoding starts as late as the set's composition PTS allows: the plane is cleared (at an epoch
+// start), then objects are decoded one after the other, then the windows are drawn
+pub(crate) fn retime_set(
+    packets: &mut [Packet],
+    pts: Timestamp,
+    presented: Option<Timestamp>,
+    windows: &mut HashMap<u8, usize>,
+) {
+    let init = match &packets[0].segment {
+        Segment::PresentationComposition(pcs) if pcs.state == CompositionState::EpochStart => {
+            transfer_time(pcs.width as usize * pcs.height as usize, RC)
+        }
+        _ => Timestamp::default(),
+    };
+
+    // the decode time of each object, counted at its last fragment
+    let mut sizes: HashMap<u16, usize> = HashMap::new();
+    let decodes: Vec<Timestamp> = packets
+        .iter()
+        .map(|packet| match &packet.segment {
+            Segment::ObjectDefinition(ods) => {
+                if ods.is_first_in_sequence {
+                    sizes.insert(ods.id, ods.width as usize * ods.height as usize);
+                }
+                match (ods.is_last_in_sequence, sizes.get(&ods.id)) {
+                    (true, Some(size)) => transfer_time(*size, RD),
+                    _ => Timestamp::default(),
+                }
+            }
+            _ => Timestamp::default(),
+        })
+        .collect();
+    for packet in packets.iter() {
+        if let Segment::WindowDefinition(defined) = &packet.segment {
+            for window in defined {
+                windows.insert(window.id, window.width as usize * window.height as usize);
+            }
+        }
+    }
+    let draw = transfer_time(windows.values().sum(), RC);
+
+    let needed = decodes
+        .iter()
+        .fold(init + draw, |sum, decode| sum + *decode);
+    let start = min(max(pts - needed, presented.unwrap_or_default()), pts);
+    let mut decoded = start + init;
+    for (packet, decode) in packets.iter_mut().zip(decodes) {
+        match &packet.segment {
+            Segment::PresentationComposition(_) => {
+                packet.pts = pts;
+                packet.dts = start;
+            }
+            Segment::WindowDefinition(_) => {
+                packet.pts = max(pts - draw, start);
+                packet.dts = start;
+            }
+            // only the last fragment of an object takes its decode time
+            Segment::ObjectDefinition(_) => {
+                packet.dts = decoded;
+                packet.pts = decoded + decode;
+                decoded = decoded + decode;
+            }
+            Segment::PaletteDefinition(_) | Segment::End => {
+                packet.pts = decoded;
+                packet.dts = decoded;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::decoder_model::check_decoder_model;
+    use crate::parser::types::{
+        CompositionObject, CompositionObjectCrop, ObjectDefinition, PaletteDefinition,
+        PresentationComposition, RLEEntry, WindowDefinition,
+    };
+    use crate::parser::validate::validate;
+
+    fn packet(pts_ms: u64, segment: Segment) -> Packet {
+        Packet {
+            pts: Timestamp::from_millis(pts_ms),
+            dts: Timestamp::default(),
+            segment,
+        }
+    }
+
+    fn pcs(number: u16, state: CompositionState, shown: bool) -> Segment {
+        let objects = match shown {
+            true => vec![CompositionObject {
+                id: 0,
+                window_id: 0,
+                x: 100,
+                y: 900,
+                crop: CompositionObjectCrop::NotCropped,
+            }],
+            false => vec![],
+        };
+        Segment::PresentationComposition(PresentationComposition {
+            width: 1920,
+            height: 1080,
+            frame_rate: 0x10,
+            number,
+            state,
+            palette_update: false,
+            palette_id: 0,
+            objects,
+        })
+    }
+
+    fn ods(id: u16, first: bool, last: bool) -> Segment {
+        Segment::ObjectDefinition(ObjectDefinition {
+            id,
+            version: 0,
+            is_last_in_sequence: last,
+            is_first_in_sequence: first,
+            width: 4,
+            height: 1,
+            data_raw: vec![
+                RLEEntry::Repeated { count: 4, color: 1 },
+                RLEEntry::EndOfLine,
+            ],
+        })
+    }
+
+    #[test]
+    fn test_repair() {
+        let packets = [
+            // outside any display set
+            packet(500, Segment::End),
+            // should start an epoch and count from 0, and is missing its END
+            packet(1_000, pcs(7, CompositionState::Normal, true)),
+            packet(
+                1_000,
+                Segment::WindowDefinition(vec![WindowDefinition {
+                    id: 0,
+                    x: 100,
+                    y: 900,
+                    width: 4,
+                    height: 1,
+                }]),
+            ),
+            packet(
+                1_000,
+                Segment::PaletteDefinition(PaletteDefinition {
+                    id: 0,
+                    version: 0,
+                    entries: vec![],
+                }),
+            ),
+            packet(1_000, ods(0, true, true)),
+            // never finished
+            packet(1_000, ods(1, true, false)),
+            packet(3_000, pcs(9, CompositionState::Normal, false)),
+            packet(3_000, Segment::End),
+        ];
+        let mut buf = Vec::new();
+        for (idx, packet) in packets.iter().enumerate() {
+            write_packet(&mut buf, packet);
+            // damage between the first two display sets
+            if idx == 5 {
+                buf.extend_from_slice(b"PG\x00\x01garbage");
+            }
+        }
+
+        let (repaired, report) = repair(&buf);
+        assert_eq!(
+            report,
+            RepairReport {
+                unreadable_bytes: 11,
+                dropped_segments: 2,
+                dropped_references: 0,
+                ends_added: 1,
+                renumbered: 2,
+                epochs_started: 1,
+                retimed: 7,
+            }
+        );
+        assert_eq!(validate(&repaired), vec![]);
+        assert_eq!(check_decoder_model(&repaired), vec![]);
+
+        // already repaired, nothing more to do
+        let (again, report) = repair(&repaired);
+        assert!(report.is_empty());
+        assert_eq!(again, repaired);
+    }
+
+    #[test]
+    fn test_repair_undefined_references() {
+        let window = Segment::WindowDefinition(vec![WindowDefinition {
+            id: 0,
+            x: 100,
+            y: 900,
+            width: 4,
+            height: 1,
+        }]);
+        let packets = [
+            // object 0 is cut short, so the composition is left showing nothing
+            packet(1_000, pcs(0, CompositionState::EpochStart, true)),
+            packet(1_000, window.clone()),
+            packet(
+                1_000,
+                Segment::PaletteDefinition(PaletteDefinition {
+                    id: 0,
+                    version: 0,
+                    entries: vec![],
+                }),
+            ),
+            packet(1_000, ods(0, true, false)),
+            packet(1_000, Segment::End),
+            // a new epoch without a palette
+            packet(3_000, pcs(1, CompositionState::EpochStart, true)),
+            packet(3_000, window),
+            packet(3_000, ods(0, true, true)),
+            packet(3_000, Segment::End),
+        ];
+        let mut buf = Vec::new();
+        for packet in &packets {
+            write_packet(&mut buf, packet);
+        }
+        assert_ne!(validate(&buf), vec![]);
+
+        let (repaired, report) = repair(&buf);
+        assert_eq!(report.dropped_segments, 1);
+        assert_eq!(report.dropped_references, 2);
+        assert_eq!(validate(&repaired), vec![]);
+        assert_eq!(check_decoder_model(&repaired), vec![]);
+    }
+
+    #[test]
+    fn test_repair_valid() {
+        // valid and decoded in time, but not as late as repair would decode it
+        let timed = |dts_ms: u64, pts_ms: u64, segment: Segment| Packet {
+            dts: Timestamp::from_millis(dts_ms),
+            ..packet(pts_ms, segment)
+        };
+        let packets = [
+            timed(850, 1_000, pcs(0, CompositionState::EpochStart, true)),
+            timed(
+                850,
+                1_000,
+                Segment::WindowDefinition(vec![WindowDefinition {
+                    id: 0,
+                    x: 100,
+                    y: 900,
+                    width: 4,
+                    height: 1,
+                }]),
+            ),
+            timed(
+                950,
+                950,
+                Segment::PaletteDefinition(PaletteDefinition {
+                    id: 0,
+                    version: 0,
+                    entries: vec![],
+                }),
+            ),
+            timed(950, 960, ods(0, true, true)),
+            timed(960, 960, Segment::End),
+            timed(1_900, 2_000, pcs(1, CompositionState::Normal, false)),
+            timed(1_900, 1_900, Segment::End),
+        ];
+        let mut buf = Vec::new();
+        for packet in &packets {
+            write_packet(&mut buf, packet);
+        }
+        assert_eq!(validate(&buf), vec![]);
+        assert_eq!(check_decoder_model(&buf), vec![]);
+
+        // every byte that changes is reported
+        let (repaired, report) = repair(&buf);
+        assert_eq!(
+            report,
+            RepairReport {
+                retimed: 7,
+                ..RepairReport::default()
+            }
+        );
+        assert_ne!(repaired, buf);
+        assert_eq!(repair(&repaired), (repaired, RepairReport::default()));
+    }
+}
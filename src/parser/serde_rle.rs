@@ -0,0 +1,54 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::parser::parse::parse_rle;
+use crate::parser::types::RLEData;
+use crate::parser::write::write_rle;
+
+// object data as the base64 of its RLE bytes, a list of runs would be most of any dump. The runs
+// are written back in their shortest encoding, so the bytes can differ from the stream's while
+// the bitmap stays the same
+pub fn serialize<S: Serializer>(data: &RLEData, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut bytes = Vec::new();
+    write_rle(&mut bytes, data);
+    serializer.serialize_str(&base64::encode(&bytes))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RLEData, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    let bytes = base64::decode(&text).map_err(D::Error::custom)?;
+    parse_rle(&bytes).ok_or_else(|| D::Error::custom("bad RLE data"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::types::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let packet = Packet {
+            pts: Timestamp::from_ticks(900_000),
+            dts: Timestamp::default(),
+            segment: Segment::ObjectDefinition(ObjectDefinition {
+                id: 0,
+                version: 0,
+                is_last_in_sequence: true,
+                is_first_in_sequence: true,
+                width: 4,
+                height: 1,
+                data_raw: vec![
+                    RLEEntry::Single(1),
+                    RLEEntry::Repeated { count: 3, color: 0 },
+                    RLEEntry::EndOfLine,
+                ],
+            }),
+        };
+
+        let json = serde_json::to_string(&packet).unwrap();
+        assert_eq!(
+            json,
+            r#"{"pts":900000,"dts":0,"segment":{"ObjectDefinition":{"id":0,"version":0,"is_last_in_sequence":true,"is_first_in_sequence":true,"width":4,"height":1,"data_raw":"AQADAAA="}}}"#
+        );
+        assert_eq!(serde_json::from_str::<Packet>(&json).unwrap(), packet);
+    }
+}
@@ -0,0 +1,468 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::parser::display_set::display_sets;
+use crate::parser::parse::{packets, packets_at};
+use crate::parser::repair::retime_set;
+use crate::parser::types::{CompositionState, Packet, Segment, Timestamp, WindowDefinition};
+use crate::parser::write::write_packet;
+use crate::retime::{retime_stream, Retime};
+use crate::Error;
+
+// what the segments of an epoch have defined so far, the last definition of each id
+#[derive(Default)]
+struct Definitions {
+    windows: BTreeMap<u8, WindowDefinition>,
+    palettes: BTreeMap<u8, Packet>,
+    // every fragment of the object
+    objects: BTreeMap<u16, Vec<Packet>>,
+}
+
+impl Definitions {
+    fn add(&mut self, packets: &[Packet]) {
+        for packet in packets {
+            match &packet.segment {
+                Segment::WindowDefinition(windows) => {
+                    for window in windows {
+                        self.windows.insert(window.id, window.clone());
+                    }
+                }
+                Segment::PaletteDefinition(pds) => {
+                    self.palettes.insert(pds.id, packet.clone());
+                }
+                Segment::ObjectDefinition(ods) => {
+                    let fragments = self.objects.entry(ods.id).or_default();
+                    if ods.is_first_in_sequence {
+                        fragments.clear();
+                    }
+                    fragments.push(packet.clone());
+                }
+                Segment::PresentationComposition(_) | Segment::End => {}
+            }
+        }
+    }
+
+    // window id -> size, as repair::retime_set takes them
+    fn window_sizes(&self) -> HashMap<u8, usize> {
+        self.windows
+            .values()
+            .map(|window| (window.id, window.width as usize * window.height as usize))
+            .collect()
+    }
+}
+
+// where a segment goes in a display set
+fn rank(segment: &Segment) -> u8 {
+    match segment {
+        Segment::PresentationComposition(_) => 0,
+        Segment::WindowDefinition(_) => 1,
+        Segment::PaletteDefinition(_) => 2,
+        Segment::ObjectDefinition(_) => 3,
+        Segment::End => 4,
+    }
+}
+
+// the display set made to start an epoch of its own: whatever the epoch defined before it and
+// the set doesn't define again is carried into it, so the sets after it still find their windows,
+// palettes and objects
+fn start_epoch(packets: &mut Vec<Packet>, defined: &Definitions) {
+    if let Segment::PresentationComposition(pcs) = &mut packets[0].segment {
+        pcs.state = CompositionState::EpochStart;
+        // an epoch start defines its palette, there's nothing to update
+        pcs.palette_update = false;
+    }
+
+    let mut own = Definitions::default();
+    own.add(packets);
+
+    let missing_windows: Vec<WindowDefinition> = defined
+        .windows
+        .values()
+        .filter(|window| !own.windows.contains_key(&window.id))
+        .cloned()
+        .collect();
+    let wds = packets
+        .iter_mut()
+        .find_map(|packet| match &mut packet.segment {
+            Segment::WindowDefinition(windows) => Some(windows),
+            _ => None,
+        });
+    match wds {
+        Some(windows) => windows.extend(missing_windows),
+        None if !missing_windows.is_empty() => packets.push(Packet {
+            segment: Segment::WindowDefinition(missing_windows),
+            ..packets[0].clone()
+        }),
+        None => {}
+    }
+
+    for (id, pds) in &defined.palettes {
+        if !own.palettes.contains_key(id) {
+            packets.push(pds.clone());
+        }
+    }
+    for (id, fragments) in &defined.objects {
+        if !own.objects.contains_key(id) {
+            packets.extend(fragments.iter().cloned());
+        }
+    }
+
+    packets.sort_by_key(|packet| rank(&packet.segment));
+}
+
+// the composition on screen when the cut starts, presented again at `from` as an epoch start
+fn show_at(
+    composition: Packet,
+    from: Timestamp,
+    defined: &Definitions,
+    out: &mut Vec<u8>,
+) -> (Packet, Timestamp) {
+    let mut packets = vec![
+        Packet {
+            pts: from,
+            dts: from,
+            ..composition
+        },
+        Packet {
+            pts: from,
+            dts: from,
+            segment: Segment::End,
+        },
+    ];
+    start_epoch(&mut packets, defined);
+    retime_set(&mut packets, from, None, &mut defined.window_sizes());
+    for packet in &packets {
+        write_packet(out, packet);
+    }
+    (packets[0].clone(), from)
+}
+
+fn shows_objects(composition: &Packet) -> bool {
+    matches!(&composition.segment, Segment::PresentationComposition(pcs) if !pcs.objects.is_empty())
+}
+
+/// The display sets presented from `from` up to (not including) `to`, cut at display set
+/// boundaries. A subtitle already showing at `from` is shown again from `from`, the first set
+/// becomes an epoch start and a composition clearing the screen is added at `to` if a subtitle is
+/// showing then. Times are kept, `retime_stream` moves them.
+pub fn cut(
+    pgs_buf: &[u8],
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+) -> Result<Vec<u8>, Error> {
+    let parsed = packets_at(pgs_buf).collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = Vec::new();
+    let mut defined = Definitions::default();
+    let mut last_pts = Timestamp::default();
+    // the composition and PTS of the last set written
+    let mut last_written: Option<(Packet, Timestamp)> = None;
+    // the composition of the last set before `from`
+    let mut showing: Option<Packet> = None;
+    let starts_inside = |from: Timestamp| to.is_none_or(|to| from < to);
+    for set in display_sets(parsed) {
+        let pts = set.packets[0].1.pts.after(last_pts);
+        for (_, packet) in &set.packets {
+            last_pts = packet.pts.after(last_pts);
+        }
+        if to.is_some_and(|to| pts >= to) {
+            break;
+        }
+        if set.composition().is_none() {
+            continue;
+        }
+
+        let starts_epoch = set.starts_epoch();
+        let mut packets: Vec<Packet> = set.packets.into_iter().map(|(_, packet)| packet).collect();
+        if starts_epoch {
+            defined = Definitions::default();
+        }
+        if from.is_some_and(|from| pts < from) {
+            defined.add(&packets);
+            showing = Some(packets[0].clone());
+            continue;
+        }
+        if let (Some(from), Some(composition)) = (from, showing.take()) {
+            if pts > from && shows_objects(&composition) {
+                last_written = Some(show_at(composition, from, &defined, &mut out));
+            }
+        }
+
+        if last_written.is_none() && !starts_epoch {
+            start_epoch(&mut packets, &defined);
+            retime_set(&mut packets, pts, None, &mut defined.window_sizes());
+        }
+        defined.add(&packets);
+        for packet in &packets {
+            write_packet(&mut out, packet);
+        }
+        last_written = Some((packets[0].clone(), pts));
+    }
+    // nothing is presented between `from` and `to`, but a subtitle can still be showing
+    if let (Some(from), Some(composition)) = (from, showing) {
+        if starts_inside(from) && shows_objects(&composition) {
+            last_written = Some(show_at(composition, from, &defined, &mut out));
+        }
+    }
+
+    if let (Some(to), Some((mut last, pts))) = (to, last_written) {
+        if let Segment::PresentationComposition(pcs) = &mut last.segment {
+            if !pcs.objects.is_empty() {
+                pcs.number = pcs.number.wrapping_add(1);
+                pcs.state = CompositionState::Normal;
+                pcs.palette_update = false;
+                pcs.objects.clear();
+
+                let mut clear = vec![
+                    last,
+                    Packet {
+                        pts: to,
+                        dts: to,
+                        segment: Segment::End,
+                    },
+                ];
+                retime_set(&mut clear, to, Some(pts), &mut defined.window_sizes());
+                for packet in &clear {
+                    write_packet(&mut out, packet);
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// The streams one after the other, each moved later by its offset. Every stream starts an epoch
+/// of its own so nothing defined in one is used by the next, and compositions are numbered again
+/// across them. The offsets should put each stream after the one before it.
+pub fn concat(streams: &[(&[u8], Timestamp)]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(streams.iter().map(|(buf, _)| buf.len()).sum());
+    let mut number: u16 = 0;
+    for (pgs_buf, offset) in streams {
+        let shifted = retime_stream(pgs_buf, &Retime::Shift(offset.ticks() as i64))?;
+
+        let mut first = true;
+        for packet in packets(&shifted) {
+            let mut packet = packet?;
+            if let Segment::PresentationComposition(pcs) = &mut packet.segment {
+                if first {
+                    pcs.state = CompositionState::EpochStart;
+                    first = false;
+                }
+                pcs.number = number;
+                number = number.wrapping_add(1);
+            }
+            write_packet(&mut out, &packet);
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::decoder_model::check_decoder_model;
+    use crate::parser::repair::repair;
+    use crate::parser::types::{
+        CompositionObject, CompositionObjectCrop, ObjectDefinition, PaletteDefinition,
+        PresentationComposition, RLEEntry,
+    };
+    use crate::parser::validate::validate;
+
+    fn pcs(number: u16, state: CompositionState, shown: bool) -> Segment {
+        let objects = match shown {
+            true => vec![CompositionObject {
+                id: 0,
+                window_id: 0,
+                x: 100,
+                y: 900,
+                crop: CompositionObjectCrop::NotCropped,
+            }],
+            false => vec![],
+        };
+        Segment::PresentationComposition(PresentationComposition {
+            width: 1920,
+            height: 1080,
+            frame_rate: 0x10,
+            number,
+            state,
+            palette_update: false,
+            palette_id: 0,
+            objects,
+        })
+    }
+
+    // an epoch showing one object at 1s, clearing it at 2s, showing it again at 3s (relying on
+    // the definitions from 1s) and clearing it at 4s, with its DTS worked out by repair
+    fn movie() -> Vec<u8> {
+        let sets = vec![
+            vec![
+                pcs(0, CompositionState::EpochStart, true),
+                Segment::WindowDefinition(vec![WindowDefinition {
+                    id: 0,
+                    x: 100,
+                    y: 900,
+                    width: 4,
+                    height: 1,
+                }]),
+                Segment::PaletteDefinition(PaletteDefinition {
+                    id: 0,
+                    version: 0,
+                    entries: vec![],
+                }),
+                Segment::ObjectDefinition(ObjectDefinition {
+                    id: 0,
+                    version: 0,
+                    is_last_in_sequence: true,
+                    is_first_in_sequence: true,
+                    width: 4,
+                    height: 1,
+                    data_raw: vec![
+                        RLEEntry::Repeated { count: 4, color: 1 },
+                        RLEEntry::EndOfLine,
+                    ],
+                }),
+                Segment::End,
+            ],
+            vec![pcs(1, CompositionState::Normal, false), Segment::End],
+            vec![pcs(2, CompositionState::Normal, true), Segment::End],
+            vec![pcs(3, CompositionState::Normal, false), Segment::End],
+        ];
+
+        let mut buf = Vec::new();
+        for (idx, segments) in sets.into_iter().enumerate() {
+            for segment in segments {
+                let packet = Packet {
+                    pts: Timestamp::from_millis(1_000 * (idx as u64 + 1)),
+                    dts: Timestamp::default(),
+                    segment,
+                };
+                write_packet(&mut buf, &packet);
+            }
+        }
+        repair(&buf).0
+    }
+
+    // (PTS in ms, composition number, state, segment names) of every display set
+    fn shape(buf: &[u8]) -> Vec<(u64, u16, CompositionState, Vec<&'static str>)> {
+        let parsed = packets_at(buf).collect::<Result<Vec<_>, _>>().unwrap();
+        display_sets(parsed)
+            .iter()
+            .map(|set| {
+                let pcs = set.composition().unwrap();
+                (
+                    set.packets[0].1.pts.as_millis(),
+                    pcs.number,
+                    pcs.state.clone(),
+                    set.packets
+                        .iter()
+                        .map(|(_, packet)| packet.segment.name())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cut() {
+        let movie = movie();
+        let part = cut(
+            &movie,
+            Some(Timestamp::from_millis(2_500)),
+            Some(Timestamp::from_millis(3_500)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            shape(&part),
+            vec![
+                (
+                    3_000,
+                    2,
+                    CompositionState::EpochStart,
+                    vec!["PCS", "WDS", "PDS", "ODS", "END"]
+                ),
+                (3_500, 3, CompositionState::Normal, vec!["PCS", "END"]),
+            ]
+        );
+        assert_eq!(validate(&part), vec![]);
+        assert_eq!(check_decoder_model(&part), vec![]);
+    }
+
+    #[test]
+    fn test_cut_mid_cue() {
+        let movie = movie();
+        let part = cut(
+            &movie,
+            Some(Timestamp::from_millis(1_500)),
+            Some(Timestamp::from_millis(3_500)),
+        )
+        .unwrap();
+
+        // the cue shown at 1s is still on screen at 1.5s
+        assert_eq!(
+            shape(&part),
+            vec![
+                (
+                    1_500,
+                    0,
+                    CompositionState::EpochStart,
+                    vec!["PCS", "WDS", "PDS", "ODS", "END"]
+                ),
+                (2_000, 1, CompositionState::Normal, vec!["PCS", "END"]),
+                (3_000, 2, CompositionState::Normal, vec!["PCS", "END"]),
+                (3_500, 3, CompositionState::Normal, vec!["PCS", "END"]),
+            ]
+        );
+        assert_eq!(validate(&part), vec![]);
+        assert_eq!(check_decoder_model(&part), vec![]);
+
+        // inside one cue, with nothing presented between the ends
+        let part = cut(
+            &movie,
+            Some(Timestamp::from_millis(3_200)),
+            Some(Timestamp::from_millis(3_800)),
+        )
+        .unwrap();
+        let shape: Vec<(u64, CompositionState)> = shape(&part)
+            .into_iter()
+            .map(|(pts, _, state, _)| (pts, state))
+            .collect();
+        assert_eq!(
+            shape,
+            vec![
+                (3_200, CompositionState::EpochStart),
+                (3_800, CompositionState::Normal),
+            ]
+        );
+        assert_eq!(validate(&part), vec![]);
+    }
+
+    #[test]
+    fn test_concat() {
+        let movie = movie();
+        let part = cut(&movie, None, Some(Timestamp::from_millis(2_500))).unwrap();
+        let joined = concat(&[
+            (&part, Timestamp::default()),
+            (&movie, Timestamp::from_millis(10_000)),
+        ])
+        .unwrap();
+
+        let shape: Vec<(u64, u16, CompositionState)> = shape(&joined)
+            .into_iter()
+            .map(|(pts, number, state, _)| (pts, number, state))
+            .collect();
+        assert_eq!(
+            shape,
+            vec![
+                (1_000, 0, CompositionState::EpochStart),
+                (2_000, 1, CompositionState::Normal),
+                (11_000, 2, CompositionState::EpochStart),
+                (12_000, 3, CompositionState::Normal),
+                (13_000, 4, CompositionState::Normal),
+                (14_000, 5, CompositionState::Normal),
+            ]
+        );
+        assert_eq!(validate(&joined), vec![]);
+    }
+}
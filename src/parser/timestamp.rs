@@ -0,0 +1,324 @@
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Ticks of the 90kHz MPEG clock in a second.
+pub const CLOCK_RATE: u64 = 90_000;
+
+// a .sup packet keeps the low 32 bits of the 33 bit PTS, so that's where its timestamps wrap
+// (every ~13.25 hours)
+const WRAP_TICKS: u64 = 1 << 32;
+
+/// A point on the 90kHz clock. Read from a packet it's the 32 bit value as stored, `after()`
+/// puts it on a timeline that keeps counting past the wrap.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Timestamp(u64);
+
+/// Frames per second as num / den, 23.976 is 24000 / 1001.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameRate {
+    pub num: u64,
+    pub den: u64,
+}
+
+impl FrameRate {
+    /// `num` / `den` frames a second.
+    pub const fn new(num: u64, den: u64) -> FrameRate {
+        FrameRate { num, den }
+    }
+
+    /// The `frame_rate` byte of a presentation composition.
+    pub fn from_pgs(value: u8) -> Option<FrameRate> {
+        match value {
+            0x10 => Some(FrameRate::new(24000, 1001)),
+            0x20 => Some(FrameRate::new(24, 1)),
+            0x30 => Some(FrameRate::new(25, 1)),
+            0x40 => Some(FrameRate::new(30000, 1001)),
+            0x60 => Some(FrameRate::new(50, 1)),
+            0x70 => Some(FrameRate::new(60000, 1001)),
+            _ => None,
+        }
+    }
+}
+
+// 24000/1001, 25, or one of the usual decimal names like 23.976
+impl FromStr for FrameRate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("bad frame rate {}", s);
+        let rate = match s {
+            "23.976" | "23.98" => FrameRate::new(24000, 1001),
+            "29.97" | "29.970" => FrameRate::new(30000, 1001),
+            "59.94" | "59.940" => FrameRate::new(60000, 1001),
+            _ => match s.split_once('/') {
+                Some((num, den)) => FrameRate::new(
+                    num.parse().map_err(|_| bad())?,
+                    den.parse().map_err(|_| bad())?,
+                ),
+                None => FrameRate::new(s.parse().map_err(|_| bad())?, 1),
+            },
+        };
+
+        if rate.num == 0 || rate.den == 0 {
+            return Err(bad());
+        }
+        Ok(rate)
+    }
+}
+
+impl Timestamp {
+    /// Ticks of the 90kHz clock.
+    pub const fn from_ticks(ticks: u64) -> Timestamp {
+        Timestamp(ticks)
+    }
+
+    /// The nearest tick, a tick is 11.1µs.
+    pub fn from_micros(us: u64) -> Timestamp {
+        Timestamp(div_round(us as u128 * CLOCK_RATE as u128, 1_000_000))
+    }
+
+    /// Exact, a millisecond is 90 ticks.
+    pub fn from_millis(ms: u64) -> Timestamp {
+        Timestamp(ms * (CLOCK_RATE / 1_000))
+    }
+
+    /// The start of the frame, frame 0 starts at 0.
+    pub fn from_frames(frames: u64, rate: FrameRate) -> Timestamp {
+        Timestamp(div_round(
+            frames as u128 * rate.den as u128 * CLOCK_RATE as u128,
+            rate.num as u128,
+        ))
+    }
+
+    /// Ticks of the 90kHz clock.
+    pub fn ticks(self) -> u64 {
+        self.0
+    }
+
+    /// The value a .sup packet stores, timestamps past the wrap come back around to 0.
+    pub fn sup_ticks(self) -> u32 {
+        (self.0 % WRAP_TICKS) as u32
+    }
+
+    /// Rounded to the nearest microsecond.
+    pub fn as_micros(self) -> u64 {
+        div_round(self.0 as u128 * 1_000_000, CLOCK_RATE as u128)
+    }
+
+    /// Rounded to the nearest millisecond.
+    pub fn as_millis(self) -> u64 {
+        div_round(self.0 as u128 * 1_000, CLOCK_RATE as u128)
+    }
+
+    /// The frame showing at this time.
+    pub fn as_frames(self, rate: FrameRate) -> u64 {
+        (self.0 as u128 * rate.num as u128 / (rate.den as u128 * CLOCK_RATE as u128)) as u64
+    }
+
+    /// The frame boundary nearest to this timestamp.
+    pub fn snap(self, rate: FrameRate) -> Timestamp {
+        let frames = div_round(
+            self.0 as u128 * rate.num as u128,
+            rate.den as u128 * CLOCK_RATE as u128,
+        );
+        Timestamp::from_frames(frames, rate)
+    }
+
+    /// This timestamp as read from a packet, placed on the timeline of the one before it. Going
+    /// back more than half the wrap period means the clock wrapped, going forward more than that
+    /// means a packet from just before a wrap came late.
+    pub fn after(self, previous: Timestamp) -> Timestamp {
+        let base = previous.0 - previous.0 % WRAP_TICKS;
+        let ticks = base + self.0 % WRAP_TICKS;
+        if ticks + WRAP_TICKS / 2 < previous.0 {
+            Timestamp(ticks + WRAP_TICKS)
+        } else if ticks > previous.0 + WRAP_TICKS / 2 && ticks >= WRAP_TICKS {
+            Timestamp(ticks - WRAP_TICKS)
+        } else {
+            Timestamp(ticks)
+        }
+    }
+}
+
+// [[hh:]mm:]ss[.mmm], the milliseconds can also follow a comma as in SRT
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("bad time {}", s);
+        let (clock, fraction) = match s.rsplit_once(['.', ',']) {
+            Some((clock, fraction)) => (clock, fraction),
+            None => (s, ""),
+        };
+
+        let mut seconds: u64 = 0;
+        for (idx, part) in clock.split(':').enumerate() {
+            if idx > 2 {
+                return Err(bad());
+            }
+            seconds = seconds * 60 + part.parse::<u64>().map_err(|_| bad())?;
+        }
+
+        let millis = match fraction.len() {
+            0 => 0,
+            1..=3 => {
+                let digits: u64 = fraction.parse().map_err(|_| bad())?;
+                digits * 10u64.pow(3 - fraction.len() as u32)
+            }
+            _ => return Err(bad()),
+        };
+
+        Ok(Timestamp::from_millis(seconds * 1_000 + millis))
+    }
+}
+
+// hh:mm:ss.mmm, what FromStr reads
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.as_millis();
+        write!(
+            f,
+            "{:02}:{:02}:{:02}.{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1_000 % 60,
+            ms % 1_000
+        )
+    }
+}
+
+// whole rates as they are, the NTSC ones to 3 decimals as in 23.976 and anything else as NUM/DEN,
+// all of them what FromStr reads
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.num.is_multiple_of(self.den) {
+            write!(f, "{}", self.num / self.den)
+        } else if self.den == 1001 && [24000, 30000, 60000].contains(&self.num) {
+            write!(f, "{:.3}", self.num as f64 / self.den as f64)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl From<u32> for Timestamp {
+    fn from(ticks: u32) -> Self {
+        Timestamp(ticks as u64)
+    }
+}
+
+impl Add for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Timestamp) -> Timestamp {
+        Timestamp(self.0 + rhs.0)
+    }
+}
+
+// the time from rhs to self, 0 when rhs is later
+impl Sub for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Timestamp) -> Timestamp {
+        Timestamp(self.0.saturating_sub(rhs.0))
+    }
+}
+
+fn div_round(n: u128, d: u128) -> u64 {
+    ((n + d / 2) / d) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        // no precision is lost below 100µs
+        assert_eq!(Timestamp::from_ticks(1).as_micros(), 11);
+        assert_eq!(Timestamp::from_ticks(8).as_micros(), 89);
+        assert_eq!(Timestamp::from_micros(89), Timestamp::from_ticks(8));
+        assert_eq!(Timestamp::from_millis(1_500).as_millis(), 1_500);
+
+        let ntsc = FrameRate::new(24000, 1001);
+        assert_eq!(
+            Timestamp::from_frames(24, ntsc),
+            Timestamp::from_ticks(90_090)
+        );
+        assert_eq!(Timestamp::from_ticks(90_090).as_frames(ntsc), 24);
+        assert_eq!(Timestamp::from_ticks(90_089).as_frames(ntsc), 23);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("23.976".parse(), Ok(FrameRate::new(24000, 1001)));
+        assert_eq!("25".parse(), Ok(FrameRate::new(25, 1)));
+        assert_eq!("30000/1001".parse(), Ok(FrameRate::new(30000, 1001)));
+        assert!("0".parse::<FrameRate>().is_err());
+
+        let ms = Timestamp::from_millis;
+        assert_eq!("01:02:03.5".parse(), Ok(ms(3_723_500)));
+        assert_eq!("01:02:03,045".parse(), Ok(ms(3_723_045)));
+        assert_eq!("2:03".parse(), Ok(ms(123_000)));
+        assert_eq!("90.25".parse(), Ok(ms(90_250)));
+        assert!("1:2:3:4".parse::<Timestamp>().is_err());
+
+        assert_eq!(ms(3_723_045).to_string(), "01:02:03.045");
+        assert_eq!(FrameRate::new(24000, 1001).to_string(), "23.976");
+        assert_eq!(FrameRate::new(25, 1).to_string(), "25");
+        assert_eq!(FrameRate::new(48000, 1001).to_string(), "48000/1001");
+    }
+
+    #[test]
+    fn test_frame_rate_round_trip() {
+        let rates: Vec<FrameRate> = (0..=u8::MAX).filter_map(FrameRate::from_pgs).collect();
+        assert_eq!(rates.len(), 6);
+        for rate in rates {
+            assert_eq!(rate.to_string().parse(), Ok(rate));
+        }
+    }
+
+    #[test]
+    fn test_snap() {
+        let ntsc = FrameRate::from_pgs(0x10).unwrap();
+        // frame 24 starts at 90090, frame 25 at 93844 (93843.75 rounded)
+        assert_eq!(
+            Timestamp::from_ticks(91_000).snap(ntsc),
+            Timestamp::from_ticks(90_090)
+        );
+        assert_eq!(
+            Timestamp::from_ticks(92_000).snap(ntsc),
+            Timestamp::from_ticks(93_844)
+        );
+
+        let pal = FrameRate::from_pgs(0x30).unwrap();
+        assert_eq!(
+            Timestamp::from_ticks(3_599).snap(pal),
+            Timestamp::from_ticks(3_600)
+        );
+        assert_eq!(FrameRate::from_pgs(0x50), None);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let before = Timestamp::from_ticks(WRAP_TICKS - 90_000);
+        let wrapped = Timestamp::from(45_000u32).after(before);
+        assert_eq!(wrapped, Timestamp::from_ticks(WRAP_TICKS + 45_000));
+        assert_eq!(wrapped - before, Timestamp::from_ticks(135_000));
+        assert_eq!(wrapped.sup_ticks(), 45_000);
+
+        // a late packet from before the wrap stays before it
+        let late = Timestamp::from((WRAP_TICKS - 1) as u32).after(wrapped);
+        assert_eq!(late, Timestamp::from_ticks(WRAP_TICKS - 1));
+
+        // no wrap, earlier packets stay earlier
+        let earlier = Timestamp::from(1_000u32).after(Timestamp::from_ticks(90_000));
+        assert_eq!(earlier, Timestamp::from_ticks(1_000));
+    }
+}
@@ -1,20 +1,32 @@
-pub type Timestamp = u32;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+pub use crate::parser::timestamp::{FrameRate, Timestamp};
+
+/// A segment with the times it's decoded and presented at.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Packet {
     pub pts: Timestamp,
     pub dts: Timestamp,
     pub segment: Segment,
 }
 
+/// How a composition relates to the ones before it.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CompositionState {
+    /// Updates the screen, everything it uses was defined earlier in the epoch.
     Normal,
+    /// Redefines everything on screen so a decoder can start here.
     AcquisitionPoint,
+    /// Clears the decoder's memory and starts a new epoch.
     EpochStart,
 }
 
+/// The segment types of a PGS stream.
 #[derive(Derivative, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derivative(Debug)]
 pub enum Segment {
     PresentationComposition(PresentationComposition),
@@ -24,10 +36,27 @@ pub enum Segment {
     End,
 }
 
+impl Segment {
+    /// The short name of the segment type as the PGS spec has it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Segment::PresentationComposition(_) => "PCS",
+            Segment::WindowDefinition(_) => "WDS",
+            Segment::PaletteDefinition(_) => "PDS",
+            Segment::ObjectDefinition(_) => "ODS",
+            Segment::End => "END",
+        }
+    }
+}
+
+/// PCS, what to show and where.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PresentationComposition {
     pub width: u16,
     pub height: u16,
+    /// Kept as the raw byte so the stream can be written back unchanged.
+    pub frame_rate: u8,
     pub number: u16,
     pub state: CompositionState,
     pub palette_update: bool,
@@ -35,14 +64,18 @@ pub struct PresentationComposition {
     pub objects: Vec<CompositionObject>,
 }
 
+/// PDS, colors for the object pixels.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PaletteDefinition {
     pub id: u8,
     pub version: u8,
     pub entries: Vec<PaletteEntry>,
 }
 
+/// ODS, a run length encoded bitmap, or a fragment of one.
 #[derive(Derivative, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derivative(Debug)]
 pub struct ObjectDefinition {
     pub id: u16,
@@ -52,10 +85,13 @@ pub struct ObjectDefinition {
     pub width: u16,
     pub height: u16,
     #[derivative(Debug = "ignore")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::parser::serde_rle"))]
     pub data_raw: RLEData,
 }
 
+/// An object placed in a window by a composition.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CompositionObject {
     pub id: u16,
     pub window_id: u8,
@@ -64,7 +100,9 @@ pub struct CompositionObject {
     pub crop: CompositionObjectCrop,
 }
 
+/// The part of the object shown.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CompositionObjectCrop {
     NotCropped,
     Cropped {
@@ -75,7 +113,9 @@ pub enum CompositionObjectCrop {
     },
 }
 
+/// A rectangle of the video frame objects are drawn in.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WindowDefinition {
     pub id: u8,
     pub x: u16,
@@ -84,13 +124,17 @@ pub struct WindowDefinition {
     pub height: u16,
 }
 
+/// The color a pixel value maps to.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PaletteEntry {
     pub id: u8,
     pub color: YCrCbAColor,
 }
 
+/// A BT.709 color with alpha.
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct YCrCbAColor {
     pub y: u8,
     pub cr: u8,
@@ -98,7 +142,9 @@ pub struct YCrCbAColor {
     pub a: u8,
 }
 
+/// One run of the object bitmap.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum RLEEntry {
     Single(u8),
 
@@ -107,4 +153,5 @@ pub enum RLEEntry {
     EndOfLine,
 }
 
+/// The runs of an object bitmap, row by row.
 pub type RLEData = Vec<RLEEntry>;
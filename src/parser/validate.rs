@@ -0,0 +1,513 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::parser::display_set::{display_sets, DisplaySet};
+use crate::parser::parse::packets_at;
+use crate::parser::renderer::rle_total_count;
+use crate::parser::types::{
+    CompositionObjectCrop, PresentationComposition, RLEEntry, Segment, Timestamp, WindowDefinition,
+};
+use crate::Error;
+
+// what a decoder has to handle at most, per presentation composition and per window definition
+const MAX_OBJECTS: usize = 2;
+const MAX_WINDOWS: usize = 2;
+
+/// A break of the HDMV PGS rules, at the byte offset of the segment (or display set) breaking it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Violation {
+    pub offset: usize,
+    pub rule: Rule,
+}
+
+/// The PGS rule a violation breaks.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Rule {
+    /// No packet can be read here, nothing after it is checked.
+    Unreadable,
+    MissingEnd,
+    /// Segments before the first composition, or between an END and the next composition.
+    NoComposition,
+    /// The RLE data doesn't decode to width x height.
+    ObjectSize {
+        id: u16,
+        width: u16,
+        height: u16,
+        pixels: usize,
+        lines: usize,
+    },
+    UndefinedObject {
+        id: u16,
+    },
+    UndefinedWindow {
+        id: u8,
+    },
+    UndefinedPalette {
+        id: u8,
+    },
+    ObjectOutsideWindow {
+        id: u16,
+        window: u8,
+    },
+    WindowOutsideFrame {
+        id: u8,
+    },
+    /// A display set presented no later than the one before it.
+    PtsNotIncreasing {
+        pts: Timestamp,
+        previous: Timestamp,
+    },
+    TooManyObjects(usize),
+    TooManyWindows(usize),
+    /// Decoder model timing, see `decoder_model`. The set can't be decoded and drawn by its PTS.
+    CompositionTooLate {
+        needed: Timestamp,
+        available: Timestamp,
+    },
+    /// Between the DTS of the object's first segment and the PTS of its last.
+    ObjectDecodeTooSlow {
+        id: u16,
+        needed: Timestamp,
+        available: Timestamp,
+    },
+    CodedDataBufferFull {
+        bytes: usize,
+    },
+    DecodedObjectBufferFull {
+        bytes: usize,
+    },
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Unreadable => write!(f, "no PGS packet can be read, the rest isn't checked"),
+            Rule::MissingEnd => write!(f, "display set without an END segment"),
+            Rule::NoComposition => write!(f, "segments outside a display set"),
+            Rule::ObjectSize {
+                id,
+                width,
+                height,
+                pixels,
+                lines,
+            } => write!(
+                f,
+                "object {} is {}x{} but its data is {} pixels in {} lines",
+                id, width, height, pixels, lines
+            ),
+            Rule::UndefinedObject { id } => write!(f, "object {} isn't defined", id),
+            Rule::UndefinedWindow { id } => write!(f, "window {} isn't defined", id),
+            Rule::UndefinedPalette { id } => write!(f, "palette {} isn't defined", id),
+            Rule::ObjectOutsideWindow { id, window } => {
+                write!(f, "object {} doesn't fit in window {}", id, window)
+            }
+            Rule::WindowOutsideFrame { id } => {
+                write!(f, "window {} doesn't fit in the video frame", id)
+            }
+            Rule::PtsNotIncreasing { pts, previous } => write!(
+                f,
+                "presented at {}, not after the display set before it at {}",
+                pts, previous
+            ),
+            Rule::TooManyObjects(count) => write!(
+                f,
+                "{} composition objects, at most {} are allowed",
+                count, MAX_OBJECTS
+            ),
+            Rule::TooManyWindows(count) => {
+                write!(f, "{} windows, at most {} are allowed", count, MAX_WINDOWS)
+            }
+            Rule::CompositionTooLate { needed, available } => write!(
+                f,
+                "needs {}ms to decode and draw but has {}ms before it's presented",
+                needed.as_millis(),
+                available.as_millis()
+            ),
+            Rule::ObjectDecodeTooSlow {
+                id,
+                needed,
+                available,
+            } => write!(
+                f,
+                "object {} needs {}ms to decode but has {}ms",
+                id,
+                needed.as_millis(),
+                available.as_millis()
+            ),
+            Rule::CodedDataBufferFull { bytes } => write!(
+                f,
+                "{} bytes of object data overflow the coded data buffer",
+                bytes
+            ),
+            Rule::DecodedObjectBufferFull { bytes } => write!(
+                f,
+                "{} bytes of decoded objects overflow the decoded object buffer",
+                bytes
+            ),
+        }
+    }
+}
+
+// what the segments of an epoch have defined so far
+#[derive(Default)]
+struct Epoch {
+    windows: HashMap<u8, WindowDefinition>,
+    palettes: HashSet<u8>,
+    // object id -> (width, height) of its last complete definition
+    objects: HashMap<u16, (u16, u16)>,
+    // object id -> (width, height, pixels, lines) of a definition split over several segments
+    fragments: HashMap<u16, (u16, u16, usize, usize)>,
+}
+
+/// Every violation in the stream in offset order. Unlike the renderer it doesn't stop at the first
+/// one, a stream is checked up to the first packet that can't be read.
+pub fn validate(pgs_buf: &[u8]) -> Vec<Violation> {
+    let mut parsed = Vec::new();
+    let mut unreadable = None;
+    for packet in packets_at(pgs_buf) {
+        match packet {
+            Ok(packet) => parsed.push(packet),
+            Err(Error::Parse { offset }) => unreadable = Some(offset),
+            Err(_) => {}
+        }
+    }
+
+    let mut violations = Vec::new();
+    let mut epoch = Epoch::default();
+    // the video frame size, from the last composition
+    let mut frame = None;
+    let mut last_pts = Timestamp::default();
+    let mut previous_pts = None;
+    for set in display_sets(parsed) {
+        let offset = set.offset();
+        let pts = set.packets[0].1.pts.after(last_pts);
+        for (_, packet) in &set.packets {
+            last_pts = packet.pts.after(last_pts);
+        }
+
+        match set.composition() {
+            Some(pcs) => {
+                if let Some(previous) = previous_pts.filter(|previous| pts <= *previous) {
+                    violations.push(Violation {
+                        offset,
+                        rule: Rule::PtsNotIncreasing { pts, previous },
+                    });
+                }
+                previous_pts = Some(pts);
+
+                if set.starts_epoch() {
+                    epoch = Epoch::default();
+                }
+                frame = Some((pcs.width, pcs.height));
+            }
+            None => violations.push(Violation {
+                offset,
+                rule: Rule::NoComposition,
+            }),
+        }
+        if !set.is_complete() {
+            violations.push(Violation {
+                offset,
+                rule: Rule::MissingEnd,
+            });
+        }
+
+        // the definitions come after the composition using them, so they're taken in first
+        check_definitions(&set, &mut epoch, frame, &mut violations);
+        if let Some(pcs) = set.composition() {
+            check_composition(offset, pcs, &epoch, &mut violations);
+        }
+    }
+
+    if let Some(offset) = unreadable {
+        violations.push(Violation {
+            offset,
+            rule: Rule::Unreadable,
+        });
+    }
+
+    violations.sort_by_key(|violation| violation.offset);
+    violations
+}
+
+fn check_definitions(
+    set: &DisplaySet,
+    epoch: &mut Epoch,
+    frame: Option<(u16, u16)>,
+    violations: &mut Vec<Violation>,
+) {
+    for (offset, packet) in &set.packets {
+        let offset = *offset;
+        match &packet.segment {
+            Segment::WindowDefinition(windows) => {
+                if windows.len() > MAX_WINDOWS {
+                    violations.push(Violation {
+                        offset,
+                        rule: Rule::TooManyWindows(windows.len()),
+                    });
+                }
+                for window in windows {
+                    if let Some((width, height)) = frame {
+                        if !fits(
+                            (window.x, window.y, window.width, window.height),
+                            (0, 0, width, height),
+                        ) {
+                            violations.push(Violation {
+                                offset,
+                                rule: Rule::WindowOutsideFrame { id: window.id },
+                            });
+                        }
+                    }
+                    epoch.windows.insert(window.id, window.clone());
+                }
+            }
+            Segment::PaletteDefinition(pds) => {
+                epoch.palettes.insert(pds.id);
+            }
+            Segment::ObjectDefinition(ods) => {
+                let pixels = rle_total_count(&ods.data_raw);
+                let lines = ods
+                    .data_raw
+                    .iter()
+                    .filter(|entry| **entry == RLEEntry::EndOfLine)
+                    .count();
+                let (width, height, pixels, lines) = match epoch.fragments.remove(&ods.id) {
+                    Some((width, height, p, l)) if !ods.is_first_in_sequence => {
+                        (width, height, p + pixels, l + lines)
+                    }
+                    _ => (ods.width, ods.height, pixels, lines),
+                };
+
+                if !ods.is_last_in_sequence {
+                    epoch
+                        .fragments
+                        .insert(ods.id, (width, height, pixels, lines));
+                    continue;
+                }
+
+                if pixels != width as usize * height as usize || lines != height as usize {
+                    violations.push(Violation {
+                        offset,
+                        rule: Rule::ObjectSize {
+                            id: ods.id,
+                            width,
+                            height,
+                            pixels,
+                            lines,
+                        },
+                    });
+                }
+                // defined at its stated size, so a bad one isn't also reported as missing
+                epoch.objects.insert(ods.id, (width, height));
+            }
+            Segment::PresentationComposition(_) | Segment::End => {}
+        }
+    }
+}
+
+fn check_composition(
+    offset: usize,
+    pcs: &PresentationComposition,
+    epoch: &Epoch,
+    violations: &mut Vec<Violation>,
+) {
+    let mut violation = |rule| violations.push(Violation { offset, rule });
+
+    if pcs.objects.len() > MAX_OBJECTS {
+        violation(Rule::TooManyObjects(pcs.objects.len()));
+    }
+    // a composition clearing the screen doesn't use its palette
+    if (!pcs.objects.is_empty() || pcs.palette_update) && !epoch.palettes.contains(&pcs.palette_id)
+    {
+        violation(Rule::UndefinedPalette { id: pcs.palette_id });
+    }
+
+    for object in &pcs.objects {
+        let window = epoch.windows.get(&object.window_id);
+        if window.is_none() {
+            violation(Rule::UndefinedWindow {
+                id: object.window_id,
+            });
+        }
+        let size = match object.crop {
+            CompositionObjectCrop::Cropped { width, height, .. } => Some((width, height)),
+            CompositionObjectCrop::NotCropped => epoch.objects.get(&object.id).copied(),
+        };
+        if !epoch.objects.contains_key(&object.id) {
+            violation(Rule::UndefinedObject { id: object.id });
+        }
+
+        if let (Some(window), Some((width, height))) = (window, size) {
+            if !fits(
+                (object.x, object.y, width, height),
+                (window.x, window.y, window.width, window.height),
+            ) {
+                violation(Rule::ObjectOutsideWindow {
+                    id: object.id,
+                    window: object.window_id,
+                });
+            }
+        }
+    }
+}
+
+// whether the (x, y, width, height) rectangle inner is within outer
+fn fits(inner: (u16, u16, u16, u16), outer: (u16, u16, u16, u16)) -> bool {
+    let (x, y, width, height) = inner;
+    let (outer_x, outer_y, outer_width, outer_height) = outer;
+    x >= outer_x
+        && y >= outer_y
+        && x as u32 + width as u32 <= outer_x as u32 + outer_width as u32
+        && y as u32 + height as u32 <= outer_y as u32 + outer_height as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::{
+        CompositionObject, CompositionState, ObjectDefinition, Packet, PaletteDefinition,
+    };
+    use crate::parser::write::write_packet;
+
+    fn stream(segments: Vec<(u64, Segment)>) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (pts_ms, segment) in segments {
+            let packet = Packet {
+                pts: Timestamp::from_millis(pts_ms),
+                dts: Timestamp::default(),
+                segment,
+            };
+            write_packet(&mut out, &packet);
+        }
+        out
+    }
+
+    fn pcs(state: CompositionState, palette_id: u8, objects: Vec<(u16, u16)>) -> Segment {
+        Segment::PresentationComposition(PresentationComposition {
+            width: 1920,
+            height: 1080,
+            frame_rate: 0x10,
+            number: 0,
+            state,
+            palette_update: false,
+            palette_id,
+            objects: objects
+                .into_iter()
+                .map(|(x, y)| CompositionObject {
+                    id: 0,
+                    window_id: 0,
+                    x,
+                    y,
+                    crop: CompositionObjectCrop::NotCropped,
+                })
+                .collect(),
+        })
+    }
+
+    fn wds(x: u16, y: u16) -> Segment {
+        Segment::WindowDefinition(vec![WindowDefinition {
+            id: 0,
+            x,
+            y,
+            width: 4,
+            height: 1,
+        }])
+    }
+
+    fn pds() -> Segment {
+        Segment::PaletteDefinition(PaletteDefinition {
+            id: 0,
+            version: 0,
+            entries: vec![],
+        })
+    }
+
+    // a 4x1 object, or 3 pixels of one when short
+    fn ods(short: bool) -> Segment {
+        Segment::ObjectDefinition(ObjectDefinition {
+            id: 0,
+            version: 0,
+            is_last_in_sequence: true,
+            is_first_in_sequence: true,
+            width: 4,
+            height: 1,
+            data_raw: vec![
+                RLEEntry::Repeated {
+                    count: if short { 3 } else { 4 },
+                    color: 1,
+                },
+                RLEEntry::EndOfLine,
+            ],
+        })
+    }
+
+    #[test]
+    fn test_valid() {
+        let buf = stream(vec![
+            (
+                1_000,
+                pcs(CompositionState::EpochStart, 0, vec![(100, 900)]),
+            ),
+            (1_000, wds(100, 900)),
+            (1_000, pds()),
+            (1_000, ods(false)),
+            (1_000, Segment::End),
+            (2_000, pcs(CompositionState::Normal, 0, vec![])),
+            (2_000, Segment::End),
+        ]);
+        assert_eq!(validate(&buf), vec![]);
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut buf = stream(vec![
+            // palette 1 is never defined, the window runs off the right of the frame and the
+            // object off the bottom of the window
+            (
+                1_000,
+                pcs(CompositionState::EpochStart, 1, vec![(1917, 901)]),
+            ),
+            (1_000, wds(1917, 900)),
+            (1_000, pds()),
+            (1_000, ods(true)),
+            // no END, and presented before the set above
+            (500, pcs(CompositionState::Normal, 0, vec![])),
+            (500, Segment::End),
+            (600, Segment::End),
+        ]);
+        buf.extend_from_slice(b"PG\0");
+
+        let rules: Vec<(usize, Rule)> = validate(&buf)
+            .into_iter()
+            .map(|violation| (violation.offset, violation.rule))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                (0, Rule::MissingEnd),
+                (0, Rule::UndefinedPalette { id: 1 }),
+                (0, Rule::ObjectOutsideWindow { id: 0, window: 0 }),
+                (32, Rule::WindowOutsideFrame { id: 0 }),
+                (
+                    70,
+                    Rule::ObjectSize {
+                        id: 0,
+                        width: 4,
+                        height: 1,
+                        pixels: 3,
+                        lines: 1
+                    }
+                ),
+                (
+                    99,
+                    Rule::PtsNotIncreasing {
+                        pts: Timestamp::from_millis(500),
+                        previous: Timestamp::from_millis(1_000)
+                    }
+                ),
+                (136, Rule::NoComposition),
+                (149, Rule::Unreadable),
+            ]
+        );
+    }
+}
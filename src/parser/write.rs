@@ -0,0 +1,278 @@
+use crate::parser::types::*;
+
+/// The inverse of `parse::get_packet`. Everything the parser keeps is written back as it was read,
+/// RLE runs get the shortest encoding for their length so the bytes can differ from the original
+/// encoder's while the bitmap stays the same.
+pub fn write_packet(out: &mut Vec<u8>, packet: &Packet) {
+    out.extend_from_slice(b"PG");
+    out.extend_from_slice(&packet.pts.sup_ticks().to_be_bytes());
+    out.extend_from_slice(&packet.dts.sup_ticks().to_be_bytes());
+
+    let mut body = Vec::new();
+    let seg_type = match &packet.segment {
+        Segment::PaletteDefinition(pds) => {
+            write_pds(&mut body, pds);
+            0x14
+        }
+        Segment::ObjectDefinition(ods) => {
+            write_ods(&mut body, ods);
+            0x15
+        }
+        Segment::PresentationComposition(pcs) => {
+            write_pcs(&mut body, pcs);
+            0x16
+        }
+        Segment::WindowDefinition(windows) => {
+            write_wds(&mut body, windows);
+            0x17
+        }
+        Segment::End => 0x80,
+    };
+
+    out.push(seg_type);
+    out.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    out.extend_from_slice(&body);
+}
+
+fn write_pds(out: &mut Vec<u8>, pds: &PaletteDefinition) {
+    out.push(pds.id);
+    out.push(pds.version);
+    for entry in &pds.entries {
+        out.extend_from_slice(&[
+            entry.id,
+            entry.color.y,
+            entry.color.cr,
+            entry.color.cb,
+            entry.color.a,
+        ]);
+    }
+}
+
+fn write_ods(out: &mut Vec<u8>, ods: &ObjectDefinition) {
+    let mut data = Vec::new();
+    write_rle(&mut data, &ods.data_raw);
+
+    let mut flags = 0;
+    if ods.is_first_in_sequence {
+        flags |= 0x80;
+    }
+    if ods.is_last_in_sequence {
+        flags |= 0x40;
+    }
+
+    out.extend_from_slice(&ods.id.to_be_bytes());
+    out.push(ods.version);
+    out.push(flags);
+    // data_size includes width & height
+    out.extend_from_slice(&(data.len() as u32 + 4).to_be_bytes()[1..]);
+    out.extend_from_slice(&ods.width.to_be_bytes());
+    out.extend_from_slice(&ods.height.to_be_bytes());
+    out.extend_from_slice(&data);
+}
+
+/// The RLE data of an object on its own, the inverse of `parse::parse_rle`.
+pub fn write_rle(out: &mut Vec<u8>, entries: &[RLEEntry]) {
+    for entry in entries {
+        write_rle_entry(out, entry);
+    }
+}
+
+fn write_rle_entry(out: &mut Vec<u8>, entry: &RLEEntry) {
+    match entry {
+        RLEEntry::Single(color) => out.push(*color),
+        RLEEntry::EndOfLine => out.extend_from_slice(&[0x00, 0x00]),
+        RLEEntry::Repeated { count, color } => {
+            out.push(0x00);
+            let long = *count > 0x3f;
+            let mut flags = 0;
+            if long {
+                flags |= 0x40;
+            }
+            if *color != 0 {
+                flags |= 0x80;
+            }
+
+            if long {
+                out.push(flags | (count >> 8) as u8 & 0x3f);
+                out.push(*count as u8);
+            } else {
+                out.push(flags | *count as u8);
+            }
+
+            if *color != 0 {
+                out.push(*color);
+            }
+        }
+    }
+}
+
+fn write_pcs(out: &mut Vec<u8>, pcs: &PresentationComposition) {
+    out.extend_from_slice(&pcs.width.to_be_bytes());
+    out.extend_from_slice(&pcs.height.to_be_bytes());
+    out.push(pcs.frame_rate);
+    out.extend_from_slice(&pcs.number.to_be_bytes());
+    out.push(match pcs.state {
+        CompositionState::Normal => 0x00,
+        CompositionState::AcquisitionPoint => 0x40,
+        CompositionState::EpochStart => 0x80,
+    });
+    out.push(if pcs.palette_update { 0x80 } else { 0x00 });
+    out.push(pcs.palette_id);
+    out.push(pcs.objects.len() as u8);
+    for obj in &pcs.objects {
+        out.extend_from_slice(&obj.id.to_be_bytes());
+        out.push(obj.window_id);
+        match &obj.crop {
+            CompositionObjectCrop::NotCropped => {
+                out.push(0x00);
+                out.extend_from_slice(&obj.x.to_be_bytes());
+                out.extend_from_slice(&obj.y.to_be_bytes());
+            }
+            CompositionObjectCrop::Cropped {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                out.push(0x40);
+                out.extend_from_slice(&obj.x.to_be_bytes());
+                out.extend_from_slice(&obj.y.to_be_bytes());
+                out.extend_from_slice(&x.to_be_bytes());
+                out.extend_from_slice(&y.to_be_bytes());
+                out.extend_from_slice(&width.to_be_bytes());
+                out.extend_from_slice(&height.to_be_bytes());
+            }
+        }
+    }
+}
+
+fn write_wds(out: &mut Vec<u8>, windows: &[WindowDefinition]) {
+    out.push(windows.len() as u8);
+    for win in windows {
+        out.push(win.id);
+        out.extend_from_slice(&win.x.to_be_bytes());
+        out.extend_from_slice(&win.y.to_be_bytes());
+        out.extend_from_slice(&win.width.to_be_bytes());
+        out.extend_from_slice(&win.height.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse::get_packet;
+    use nom::error::VerboseError;
+
+    fn packets() -> Vec<Packet> {
+        let segments = vec![
+            Segment::PresentationComposition(PresentationComposition {
+                width: 1920,
+                height: 1080,
+                frame_rate: 0x10,
+                number: 7,
+                state: CompositionState::EpochStart,
+                palette_update: false,
+                palette_id: 0,
+                objects: vec![
+                    CompositionObject {
+                        id: 0,
+                        window_id: 0,
+                        x: 640,
+                        y: 940,
+                        crop: CompositionObjectCrop::NotCropped,
+                    },
+                    CompositionObject {
+                        id: 1,
+                        window_id: 1,
+                        x: 640,
+                        y: 40,
+                        crop: CompositionObjectCrop::Cropped {
+                            x: 0,
+                            y: 0,
+                            width: 10,
+                            height: 2,
+                        },
+                    },
+                ],
+            }),
+            Segment::WindowDefinition(vec![
+                WindowDefinition {
+                    id: 0,
+                    x: 640,
+                    y: 940,
+                    width: 640,
+                    height: 96,
+                },
+                WindowDefinition {
+                    id: 1,
+                    x: 640,
+                    y: 40,
+                    width: 640,
+                    height: 96,
+                },
+            ]),
+            Segment::PaletteDefinition(PaletteDefinition {
+                id: 0,
+                version: 1,
+                entries: vec![PaletteEntry {
+                    id: 1,
+                    color: YCrCbAColor {
+                        y: 235,
+                        cr: 128,
+                        cb: 128,
+                        a: 255,
+                    },
+                }],
+            }),
+            Segment::ObjectDefinition(ObjectDefinition {
+                id: 0,
+                version: 0,
+                is_last_in_sequence: true,
+                is_first_in_sequence: true,
+                width: 300,
+                height: 2,
+                data_raw: vec![
+                    RLEEntry::Single(1),
+                    RLEEntry::Repeated { count: 3, color: 0 },
+                    RLEEntry::Repeated {
+                        count: 200,
+                        color: 0,
+                    },
+                    RLEEntry::Repeated { count: 4, color: 1 },
+                    RLEEntry::Repeated {
+                        count: 92,
+                        color: 1,
+                    },
+                    RLEEntry::EndOfLine,
+                    RLEEntry::Repeated {
+                        count: 300,
+                        color: 1,
+                    },
+                    RLEEntry::EndOfLine,
+                ],
+            }),
+            Segment::End,
+        ];
+
+        segments
+            .into_iter()
+            .enumerate()
+            .map(|(idx, segment)| Packet {
+                pts: Timestamp::from_ticks(900_000 + idx as u64),
+                dts: Timestamp::default(),
+                segment,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for packet in packets() {
+            let mut out = Vec::new();
+            write_packet(&mut out, &packet);
+            let (rest, parsed) = get_packet::<VerboseError<&[u8]>>(&out).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(parsed, packet);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use cap_parser::parser::repair::{repair, RepairReport};
+
+use crate::{create_output, read_input, Failure};
+
+pub struct RepairOptions {
+    // cli::STDIO for stdin
+    pub input: PathBuf,
+    // cli::STDIO for stdout
+    pub output: PathBuf,
+    // -1 prints nothing, 0 what was repaired
+    pub verbosity: i8,
+}
+
+// a stream is always written back, the report says what changed in it
+pub fn run(options: &RepairOptions) -> Result<(), Failure> {
+    let buffer = read_input(&options.input)?;
+    let (repaired, report) = repair(&buffer);
+
+    let mut out = create_output(&options.output)?;
+    out.write_all(&repaired)?;
+    out.flush()?;
+
+    if options.verbosity >= 0 {
+        eprintln!("{}", describe(&report));
+    }
+    Ok(())
+}
+
+fn describe(report: &RepairReport) -> String {
+    if report.is_empty() {
+        return "nothing to repair".to_string();
+    }
+
+    let changes = [
+        (report.unreadable_bytes, "unreadable bytes skipped"),
+        (report.dropped_segments, "segments dropped"),
+        (report.dropped_references, "undefined references dropped"),
+        (report.ends_added, "END segments added"),
+        (report.renumbered, "compositions renumbered"),
+        (report.epochs_started, "compositions made epoch starts"),
+        (report.retimed, "segments retimed"),
+    ];
+    let parts: Vec<String> = changes
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(&RepairReport::default()), "nothing to repair");
+        let report = RepairReport {
+            unreadable_bytes: 11,
+            ends_added: 1,
+            retimed: 4,
+            ..RepairReport::default()
+        };
+        assert_eq!(
+            describe(&report),
+            "11 unreadable bytes skipped, 1 END segments added, 4 segments retimed"
+        );
+    }
+}
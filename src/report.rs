@@ -0,0 +1,179 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::ocr::ScreenText;
+use crate::subtitle::srt::format_timestamp_microsec;
+
+/// Where to list the cues that should be checked by hand.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReviewOptions {
+    /// Cues with a mean word confidence below this (0 to 100) are listed for review.
+    pub threshold: f32,
+    pub path: PathBuf,
+    /// The rendered image of every listed cue is saved here so it can be checked by eye.
+    pub image_dir: PathBuf,
+}
+
+impl ReviewOptions {
+    /// The cue's confidence is below the threshold.
+    pub fn needs_review(&self, screen: &ScreenText) -> bool {
+        screen.confidence() < self.threshold
+    }
+
+    /// Named after the screen hash, repeats of a screen share the image of the first one.
+    pub fn image_path(&self, screen: &ScreenText) -> PathBuf {
+        self.image_dir.join(format!("sub-{:016x}.png", screen.hash))
+    }
+}
+
+/// Every cue and line with its OCR confidence, as JSON.
+pub fn write_confidence_json<W: Write>(mut out: W, screens: &[ScreenText]) -> io::Result<()> {
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"cues\": [")?;
+    for (idx, screen) in screens.iter().enumerate() {
+        writeln!(out, "    {{")?;
+        writeln!(out, "      \"number\": {},", screen.number)?;
+        writeln!(out, "      \"start_us\": {},", screen.begin_us)?;
+        writeln!(out, "      \"end_us\": {},", screen.end_us)?;
+        writeln!(out, "      \"confidence\": {:.2},", screen.confidence())?;
+        writeln!(out, "      \"lines\": [")?;
+        for (line_idx, line) in screen.lines.iter().enumerate() {
+            writeln!(
+                out,
+                "        {{ \"text\": {}, \"confidence\": {:.2} }}{}",
+                json_string(&line.text()),
+                line.confidence(),
+                trailing_comma(line_idx, screen.lines.len())
+            )?;
+        }
+        writeln!(out, "      ]")?;
+        writeln!(out, "    }}{}", trailing_comma(idx, screens.len()))?;
+    }
+    writeln!(out, "  ]")?;
+    writeln!(out, "}}")
+}
+
+/// The cues below the threshold, each with the path of its image.
+pub fn write_review<W: Write>(
+    mut out: W,
+    review: &ReviewOptions,
+    screens: &[ScreenText],
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "# cues with a mean OCR confidence below {:.0}",
+        review.threshold
+    )?;
+    for screen in screens.iter().filter(|screen| review.needs_review(screen)) {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{}\t{} --> {}\t{:.2}\t{}",
+            screen.number,
+            format_timestamp_microsec(screen.begin_us),
+            format_timestamp_microsec(screen.end_us),
+            screen.confidence(),
+            review.image_path(screen).display()
+        )?;
+        for line in &screen.lines {
+            writeln!(out, "\t{:.2}\t{}", line.confidence(), line.text())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn trailing_comma(idx: usize, len: usize) -> &'static str {
+    if idx + 1 < len {
+        ","
+    } else {
+        ""
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr::italic::Bounds;
+    use crate::ocr::{OcrLine, OcrWord};
+    use crate::subtitle::{CueColors, Position};
+
+    fn screen(text: &str) -> ScreenText {
+        ScreenText {
+            number: 1,
+            begin_us: 1_000_000,
+            end_us: 2_500_000,
+            hash: 0,
+            position: Position {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            },
+            windows: vec![],
+            video_width: 1920,
+            video_height: 1080,
+            colors: CueColors::default(),
+            lines: vec![OcrLine {
+                words: vec![OcrWord {
+                    text: text.to_string(),
+                    italic: false,
+                    confidence: 75.0,
+                    bounds: Bounds {
+                        left: 0,
+                        top: 0,
+                        width: 0,
+                        height: 0,
+                    },
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(
+            json_string("\"quoted\" C:\\dir"),
+            "\"\\\"quoted\\\" C:\\\\dir\""
+        );
+        assert_eq!(
+            json_string("a\nb\r\tc\u{0}\u{1b}\u{7f}é"),
+            "\"a\\nb\\r\\tc\\u0000\\u001b\u{7f}é\""
+        );
+    }
+
+    #[test]
+    fn test_confidence_json() {
+        let mut out = Vec::new();
+        write_confidence_json(&mut out, &[screen("\"Hi\"\\"), screen("\u{1}")]).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.contains("{ \"text\": \"\\\"Hi\\\"\\\\\", \"confidence\": 75.00 }"));
+
+        #[cfg(feature = "serde")]
+        {
+            let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed["cues"][0]["lines"][0]["text"], "\"Hi\"\\");
+            assert_eq!(parsed["cues"][1]["lines"][0]["text"], "\u{1}");
+            assert_eq!(parsed["cues"][1]["end_us"], 2_500_000);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::convert::TryFrom;
+
+use crate::parser::parse::packets;
+use crate::parser::types::{FrameRate, Timestamp};
+use crate::parser::write::write_packet;
+use crate::Error;
+
+/// Ways of fixing the sync of a stream, all in 90kHz ticks so nothing is lost to rounding through
+/// microseconds.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Retime {
+    /// Every timestamp moved by this many ticks.
+    Shift(i64),
+    /// Every timestamp multiplied by num / den.
+    Scale { num: i64, den: i64 },
+    /// Timed for video at one frame rate, played with video at another. From 24000 / 1001 to
+    /// 25 / 1 is a PAL speedup.
+    FrameRate { from: FrameRate, to: FrameRate },
+    /// Two (old, new) pairs, the stream is stretched so both old timestamps land on the new ones.
+    Sync {
+        first: (Timestamp, Timestamp),
+        second: (Timestamp, Timestamp),
+    },
+}
+
+impl Retime {
+    // (origin, num, den, offset) for t' = offset + (t - origin) * num / den, None when den is 0
+    // (equal sync points, a rate of 0)
+    fn linear(&self) -> Option<(i128, i128, i128, i128)> {
+        let (origin, num, den, offset) = match *self {
+            Retime::Shift(ticks) => (0, 1, 1, ticks as i128),
+            Retime::Scale { num, den } => (0, num as i128, den as i128, 0),
+            Retime::FrameRate { from, to } => (
+                0,
+                from.num as i128 * to.den as i128,
+                from.den as i128 * to.num as i128,
+                0,
+            ),
+            Retime::Sync { first, second } => (
+                first.0.ticks() as i128,
+                second.1.ticks() as i128 - first.1.ticks() as i128,
+                second.0.ticks() as i128 - first.0.ticks() as i128,
+                first.1.ticks() as i128,
+            ),
+        };
+
+        match den {
+            0 => None,
+            den if den < 0 => Some((origin, -num, -den, offset)),
+            _ => Some((origin, num, den, offset)),
+        }
+    }
+
+    /// Rounded to the nearest tick. None when it lands before 0 or past what a timestamp can
+    /// hold, or the retime doesn't map times at all (equal sync points, a rate of 0).
+    pub fn apply(&self, ts: Timestamp) -> Option<Timestamp> {
+        let (origin, num, den, offset) = self.linear()?;
+        let scaled = (ts.ticks() as i128 - origin) * num;
+        let mut quotient = scaled.div_euclid(den);
+        if 2 * scaled.rem_euclid(den) >= den {
+            quotient += 1;
+        }
+
+        u64::try_from(offset + quotient)
+            .ok()
+            .map(Timestamp::from_ticks)
+    }
+}
+
+/// Every packet of the stream written back with its PTS and DTS retimed. Fails with
+/// `Error::Retime` rather than moving any of them before 0.
+pub fn retime_stream(pgs_buf: &[u8], retime: &Retime) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(pgs_buf.len());
+
+    // timestamps are retimed on the unwrapped timeline and wrap again when written
+    let mut last_pts: Option<Timestamp> = None;
+    for packet in packets(pgs_buf) {
+        let mut packet = packet?;
+        let pts = match last_pts {
+            Some(last) => packet.pts.after(last),
+            None => packet.pts,
+        };
+        last_pts = Some(pts);
+
+        let out_of_range = || Error::Retime { pts };
+        packet.pts = retime.apply(pts).ok_or_else(out_of_range)?;
+        // most muxers leave the DTS at 0, that means unset rather than the start
+        if packet.dts != Timestamp::default() {
+            packet.dts = retime
+                .apply(packet.dts.after(pts))
+                .ok_or_else(out_of_range)?;
+        }
+        write_packet(&mut out, &packet);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::{Packet, Segment};
+
+    #[test]
+    fn test_shift() {
+        let ts = Timestamp::from_ticks;
+        assert_eq!(Retime::Shift(90_000).apply(ts(1)), Some(ts(90_001)));
+        assert_eq!(Retime::Shift(-90_000).apply(ts(1)), None);
+        assert_eq!(Retime::Scale { num: -1, den: 1 }.apply(ts(1)), None);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let ts = Timestamp::from_ticks;
+        // equal old times give no way to map anything
+        let sync = Retime::Sync {
+            first: (ts(90_000), ts(180_000)),
+            second: (ts(90_000), ts(270_000)),
+        };
+        assert_eq!(sync.apply(ts(90_000)), None);
+
+        let mut buf = Vec::new();
+        write_packet(
+            &mut buf,
+            &Packet {
+                pts: ts(45_000),
+                dts: Timestamp::default(),
+                segment: Segment::End,
+            },
+        );
+        assert!(retime_stream(&buf, &Retime::Shift(-45_000)).is_ok());
+        assert!(matches!(
+            retime_stream(&buf, &Retime::Shift(-45_001)),
+            Err(Error::Retime { pts }) if pts == ts(45_000)
+        ));
+    }
+
+    #[test]
+    fn test_frame_rate() {
+        let ts = Timestamp::from_ticks;
+        let speedup = Retime::FrameRate {
+            from: FrameRate::new(24000, 1001),
+            to: FrameRate::new(25, 1),
+        };
+        // one hour at 23.976 plays in 57:32.547 at 25
+        assert_eq!(speedup.apply(ts(324_000_000)), Some(ts(310_729_271)));
+        assert_eq!(speedup.apply(ts(25025)), Some(ts(24000)));
+    }
+
+    #[test]
+    fn test_sync() {
+        let ts = Timestamp::from_ticks;
+        let sync = Retime::Sync {
+            first: (ts(90_000), ts(180_000)),
+            second: (ts(900_000), ts(1_260_000)),
+        };
+        assert_eq!(sync.apply(ts(90_000)), Some(ts(180_000)));
+        assert_eq!(sync.apply(ts(900_000)), Some(ts(1_260_000)));
+        assert_eq!(sync.apply(ts(495_000)), Some(ts(720_000)));
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use cap_parser::parser::splice::{concat, cut};
+use cap_parser::parser::types::Timestamp;
+use cap_parser::parser::validate::{validate, Rule};
+
+use crate::{create_output, read_input, Failure};
+
+pub struct CutOptions {
+    // cli::STDIO for stdin
+    pub input: PathBuf,
+    // cli::STDIO for stdout
+    pub output: PathBuf,
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+}
+
+pub struct MergeOptions {
+    // each stream with the offset its timestamps are moved by
+    pub inputs: Vec<(PathBuf, Timestamp)>,
+    // cli::STDIO for stdout
+    pub output: PathBuf,
+}
+
+pub fn run_cut(options: &CutOptions) -> Result<(), Failure> {
+    let buffer = read_input(&options.input)?;
+    let part = cut(&buffer, options.from, options.to)?;
+
+    let mut out = create_output(&options.output)?;
+    out.write_all(&part)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn run_merge(options: &MergeOptions) -> Result<(), Failure> {
+    let buffers = options
+        .inputs
+        .iter()
+        .map(|(input, offset)| Ok((read_input(input)?, *offset)))
+        .collect::<Result<Vec<_>, Failure>>()?;
+    let streams: Vec<(&[u8], Timestamp)> = buffers
+        .iter()
+        .map(|(buffer, offset)| (buffer.as_slice(), *offset))
+        .collect();
+    let merged = concat(&streams)?;
+
+    // still written, the offsets may be meant to be fixed with a retime afterwards
+    let overlaps = validate(&merged)
+        .iter()
+        .filter(|violation| matches!(violation.rule, Rule::PtsNotIncreasing { .. }))
+        .count();
+    if overlaps > 0 {
+        eprintln!(
+            "warning: {} display sets are presented before the one ahead of them, the offsets \
+             overlap the streams",
+            overlaps
+        );
+    }
+
+    let mut out = create_output(&options.output)?;
+    out.write_all(&merged)?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::io::{self, Write};
+
+use crate::subtitle::{split_timestamp, Cue, CueLine, SubtitleDocument, SubtitleWriter};
+
+// colors of the Default style, cues drawn in anything else get \c and \3c overrides
+const DEFAULT_FILL: [u8; 3] = [255, 255, 255];
+const DEFAULT_OUTLINE: [u8; 3] = [0, 0, 0];
+// font size as a share of the video height
+const FONT_SIZE_SHARE: f64 = 0.05;
+
+/// Advanced SubStation Alpha, cues placed and colored like the bitmaps.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AssWriter;
+
+impl SubtitleWriter for AssWriter {
+    fn write(&self, document: &SubtitleDocument, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "[Script Info]")?;
+        writeln!(out, "ScriptType: v4.00+")?;
+        writeln!(out, "PlayResX: {}", document.width)?;
+        writeln!(out, "PlayResY: {}", document.height)?;
+        // lines are broken where the bitmap broke them
+        writeln!(out, "WrapStyle: 2")?;
+        writeln!(out, "ScaledBorderAndShadow: yes")?;
+        writeln!(out)?;
+
+        writeln!(out, "[V4+ Styles]")?;
+        writeln!(
+            out,
+            "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+             BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+             BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding"
+        )?;
+        writeln!(
+            out,
+            "Style: Default,Arial,{},&H00{},&H000000FF,&H00{},&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1",
+            (document.height as f64 * FONT_SIZE_SHARE).round(),
+            bgr(DEFAULT_FILL),
+            bgr(DEFAULT_OUTLINE)
+        )?;
+        writeln!(out)?;
+
+        writeln!(out, "[Events]")?;
+        writeln!(
+            out,
+            "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text"
+        )?;
+
+        let mut cues: Vec<_> = document.cues.iter().collect();
+        cues.sort_by_key(|cue| (cue.start_us, cue.end_us));
+        for cue in cues {
+            writeln!(
+                out,
+                "Dialogue: 0,{},{},Default,,0,0,0,,{}{}",
+                format_timestamp(cue.start_us),
+                format_timestamp(cue.end_us),
+                overrides(document, cue),
+                cue.lines
+                    .iter()
+                    .map(format_line)
+                    .collect::<Vec<String>>()
+                    .join("\\N")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// anchors the cue where the bitmap was: bottom center in the lower half of the frame, top
+// center in the upper half, plus the bitmap's colors when they aren't the style's
+fn overrides(document: &SubtitleDocument, cue: &Cue) -> String {
+    let pos = &cue.position;
+    let center_x = pos.x + pos.width / 2;
+    let mut tags = if pos.y + pos.height / 2 > document.height / 2 {
+        format!("\\an2\\pos({},{})", center_x, pos.y + pos.height)
+    } else {
+        format!("\\an8\\pos({},{})", center_x, pos.y)
+    };
+
+    let colors = &cue.style.colors;
+    if let Some(fill) = colors.fill.filter(|c| *c != DEFAULT_FILL) {
+        tags.push_str(&format!("\\c&H{}&", bgr(fill)));
+    }
+    if let Some(outline) = colors.outline.filter(|c| *c != DEFAULT_OUTLINE) {
+        tags.push_str(&format!("\\3c&H{}&", bgr(outline)));
+    }
+
+    format!("{{{}}}", tags)
+}
+
+fn format_line(line: &CueLine) -> String {
+    line.spans
+        .iter()
+        .map(|span| {
+            let text = escape(&span.text);
+            if span.italic {
+                format!("{{\\i1}}{}{{\\i0}}", text)
+            } else {
+                text
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// braces would open an override block
+fn escape(text: &str) -> String {
+    text.replace('{', "\\{").replace('}', "\\}")
+}
+
+// ASS colors are written blue, green, red
+fn bgr(rgb: [u8; 3]) -> String {
+    format!("{:02X}{:02X}{:02X}", rgb[2], rgb[1], rgb[0])
+}
+
+/// Microseconds as H:MM:SS.cc.
+pub fn format_timestamp(us: u64) -> String {
+    let (hours, minutes, seconds, milliseconds) = split_timestamp(us);
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        hours,
+        minutes,
+        seconds,
+        milliseconds / 10
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subtitle::fixtures::positioned_document;
+
+    #[test]
+    fn test_golden() {
+        let mut out = Vec::new();
+        AssWriter.write(&positioned_document(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            include_str!("../../tests/golden/basic.ass")
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::subtitle::{Cue, CueColors, CueLine, CueStyle, Position, Span, SubtitleDocument};
+
+fn cue(
+    start_us: u64,
+    end_us: u64,
+    position: Position,
+    window: Position,
+    lines: &[&[(&str, bool)]],
+) -> Cue {
+    let lines: Vec<CueLine> = lines
+        .iter()
+        .map(|spans| CueLine {
+            spans: spans
+                .iter()
+                .map(|(text, italic)| Span {
+                    text: text.to_string(),
+                    italic: *italic,
+                })
+                .collect(),
+            confidence: 90.0,
+        })
+        .collect();
+    let italic = lines
+        .iter()
+        .all(|line| line.spans.iter().all(|span| span.italic));
+
+    Cue {
+        start_us,
+        end_us,
+        lines,
+        position,
+        windows: vec![window],
+        style: CueStyle {
+            italic,
+            colors: CueColors {
+                fill: Some([255, 255, 0]),
+                outline: Some([0, 0, 0]),
+            },
+        },
+        confidence: 90.0,
+    }
+}
+
+// out of order on purpose, writers sort before numbering
+pub fn document() -> SubtitleDocument {
+    let frame = Position {
+        x: 0,
+        y: 0,
+        width: 1920,
+        height: 1080,
+    };
+
+    SubtitleDocument {
+        width: 1920,
+        height: 1080,
+        cues: vec![
+            cue(
+                62_005_000,
+                64_500_000,
+                frame,
+                frame,
+                &[&[("Second cue.", false)]],
+            ),
+            cue(
+                1_000,
+                2_345_678,
+                frame,
+                frame,
+                &[&[("First", false), ("cue", true)], &[("two lines", false)]],
+            ),
+            cue(
+                3_723_004_000,
+                3_725_000_999,
+                frame,
+                frame,
+                &[&[("Over an hour in.", true)]],
+            ),
+        ],
+    }
+}
+
+// document() placed at the top and bottom of the frame, with text that needs escaping, for the
+// formats that keep positions
+pub fn positioned_document() -> SubtitleDocument {
+    let bottom = Position {
+        x: 560,
+        y: 900,
+        width: 800,
+        height: 120,
+    };
+    let top = Position {
+        x: 760,
+        y: 60,
+        width: 400,
+        height: 60,
+    };
+    let bottom_window = Position {
+        x: 192,
+        y: 864,
+        width: 1536,
+        height: 192,
+    };
+    let top_window = Position {
+        x: 192,
+        y: 40,
+        width: 1536,
+        height: 100,
+    };
+
+    SubtitleDocument {
+        width: 1920,
+        height: 1080,
+        cues: vec![
+            cue(
+                62_005_000,
+                64_500_000,
+                top,
+                top_window,
+                &[&[("Second & <cue>.", false)]],
+            ),
+            cue(
+                1_000,
+                2_345_678,
+                bottom,
+                bottom_window,
+                &[&[("First", false), ("cue", true)], &[("two lines", false)]],
+            ),
+            cue(
+                3_723_004_000,
+                3_725_000_999,
+                bottom,
+                bottom_window,
+                &[&[("Over an hour in.", true)]],
+            ),
+        ],
+    }
+}
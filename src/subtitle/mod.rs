@@ -0,0 +1,275 @@
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use image::RgbaImage;
+
+use crate::ocr::{OcrLine, ScreenText};
+
+/// Advanced SubStation Alpha.
+pub mod ass;
+/// SubRip.
+pub mod srt;
+/// Timed Text, IMSC1 text profile.
+pub mod ttml;
+/// Timed Text, IMSC1 image profile.
+#[cfg(feature = "image")]
+pub mod ttml_image;
+/// DVD subpictures.
+pub mod vobsub;
+/// WebVTT.
+pub mod vtt;
+
+#[cfg(test)]
+mod fixtures;
+
+/// A run of words sharing the same style, spans of a line are separated by a single space.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Span {
+    pub text: String,
+    pub italic: bool,
+}
+
+/// A line of a cue.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CueLine {
+    pub spans: Vec<Span>,
+    /// Mean OCR confidence of the words on the line, 0 to 100.
+    pub confidence: f32,
+}
+
+/// Where the subtitle bitmap sits on the video frame, in pixels.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Position {
+    /// The smallest rectangle covering both.
+    pub fn union(&self, other: &Position) -> Position {
+        let x = min(self.x, other.x);
+        let y = min(self.y, other.y);
+        let right = max(self.x + self.width, other.x + other.width);
+        let bottom = max(self.y + self.height, other.y + other.height);
+
+        Position {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+/// How the cue is drawn.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct CueStyle {
+    /// Every line of the cue is italic.
+    pub italic: bool,
+    pub colors: CueColors,
+}
+
+/// The colors the subtitle bitmap is mostly drawn in, as rgb.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct CueColors {
+    pub fill: Option<[u8; 3]>,
+    pub outline: Option<[u8; 3]>,
+}
+
+const MIN_ALPHA: u8 = 0x80;
+
+/// The two most used opaque colors of the image, the brighter one taken as the text fill and the
+/// darker one as its outline.
+pub fn dominant_colors(image: &RgbaImage) -> CueColors {
+    let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+    for p in image.pixels().filter(|p| p[3] >= MIN_ALPHA) {
+        *counts.entry([p[0], p[1], p[2]]).or_insert(0) += 1;
+    }
+
+    let mut by_count: Vec<([u8; 3], usize)> = counts.into_iter().collect();
+    by_count.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let luma = |c: &[u8; 3]| c[0] as u32 * 299 + c[1] as u32 * 587 + c[2] as u32 * 114;
+    match (by_count.first(), by_count.get(1)) {
+        (Some((a, _)), Some((b, _))) if luma(a) >= luma(b) => CueColors {
+            fill: Some(*a),
+            outline: Some(*b),
+        },
+        (Some((a, _)), Some((b, _))) => CueColors {
+            fill: Some(*b),
+            outline: Some(*a),
+        },
+        (Some((a, _)), None) => CueColors {
+            fill: Some(*a),
+            outline: None,
+        },
+        _ => CueColors::default(),
+    }
+}
+
+/// Text shown from `start_us` to `end_us`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cue {
+    pub start_us: u64,
+    pub end_us: u64,
+    pub lines: Vec<CueLine>,
+    pub position: Position,
+    /// The PGS windows the cue was drawn in.
+    pub windows: Vec<Position>,
+    pub style: CueStyle,
+    pub confidence: f32,
+}
+
+// used when there are no screens to take the video size from
+const DEFAULT_VIDEO_WIDTH: u32 = 1920;
+const DEFAULT_VIDEO_HEIGHT: u32 = 1080;
+
+/// The cues of a stream, ready for a text subtitle writer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SubtitleDocument {
+    /// Size of the video frame cue positions are relative to.
+    pub width: u32,
+    pub height: u32,
+    pub cues: Vec<Cue>,
+}
+
+impl SubtitleDocument {
+    /// A cue for each screen, in the same order.
+    pub fn from_screens(screens: &[ScreenText]) -> SubtitleDocument {
+        let (width, height) = screens
+            .first()
+            .map(|screen| (screen.video_width, screen.video_height))
+            .unwrap_or((DEFAULT_VIDEO_WIDTH, DEFAULT_VIDEO_HEIGHT));
+
+        SubtitleDocument {
+            width,
+            height,
+            cues: screens
+                .iter()
+                .map(|screen| {
+                    let lines: Vec<CueLine> = screen.lines.iter().map(cue_line).collect();
+                    let italic = lines
+                        .iter()
+                        .all(|line| line.spans.iter().all(|span| span.italic));
+
+                    Cue {
+                        start_us: screen.begin_us,
+                        end_us: screen.end_us,
+                        lines,
+                        position: screen.position,
+                        windows: screen.windows.clone(),
+                        style: CueStyle {
+                            italic,
+                            colors: screen.colors,
+                        },
+                        confidence: screen.confidence(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+fn cue_line(line: &OcrLine) -> CueLine {
+    let mut spans: Vec<Span> = Vec::new();
+    for word in &line.words {
+        match spans.last_mut() {
+            Some(span) if span.italic == word.italic => {
+                span.text.push(' ');
+                span.text.push_str(&word.text);
+            }
+            _ => spans.push(Span {
+                text: word.text.clone(),
+                italic: word.italic,
+            }),
+        }
+    }
+
+    CueLine {
+        spans,
+        confidence: line.confidence(),
+    }
+}
+
+/// The line ending a text subtitle is written with.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The bytes of the line ending.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// The language tag written into TTML and .idx files for a tesseract language, the first one when
+/// several are joined by +. Languages without a two letter code keep the tesseract name.
+pub fn language_tag(language: &str) -> String {
+    let first = language.split('+').next().unwrap_or(language);
+    let tag = match first {
+        "ara" => "ar",
+        "chi_sim" => "zh-Hans",
+        "chi_tra" => "zh-Hant",
+        "ces" => "cs",
+        "dan" => "da",
+        "deu" => "de",
+        "ell" => "el",
+        "eng" => "en",
+        "fin" => "fi",
+        "fra" => "fr",
+        "heb" => "he",
+        "hun" => "hu",
+        "ita" => "it",
+        "jpn" => "ja",
+        "kor" => "ko",
+        "nld" => "nl",
+        "nor" => "no",
+        "pol" => "pl",
+        "por" => "pt",
+        "ron" => "ro",
+        "rus" => "ru",
+        "spa" => "es",
+        "swe" => "sv",
+        "tur" => "tr",
+        "ukr" => "uk",
+        other => other,
+    };
+    tag.to_string()
+}
+
+// (hours, minutes, seconds, milliseconds)
+fn split_timestamp(us: u64) -> (u64, u64, u64, u64) {
+    const MS_PER_MICRO: u64 = 1_000;
+    const SEC_PER_MICRO: u64 = MS_PER_MICRO * 1_000;
+    const MIN_PER_MICRO: u64 = SEC_PER_MICRO * 60;
+    const HR_PER_MICRO: u64 = MIN_PER_MICRO * 60;
+
+    let mut remain = us;
+
+    let hours = remain / HR_PER_MICRO;
+    remain %= HR_PER_MICRO;
+
+    let minutes = remain / MIN_PER_MICRO;
+    remain %= MIN_PER_MICRO;
+
+    let seconds = remain / SEC_PER_MICRO;
+    remain %= SEC_PER_MICRO;
+
+    (hours, minutes, seconds, remain / MS_PER_MICRO)
+}
+
+/// A text subtitle format.
+pub trait SubtitleWriter {
+    /// Writes every cue of the document.
+    fn write(&self, document: &SubtitleDocument, out: &mut dyn Write) -> io::Result<()>;
+}
@@ -0,0 +1,109 @@
+use std::io::{self, Write};
+
+use crate::subtitle::{split_timestamp, CueLine, LineEnding, SubtitleDocument, SubtitleWriter};
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// SubRip, italic spans kept as `<i>` tags.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SrtWriter {
+    pub line_ending: LineEnding,
+    /// Some players on Windows only pick up the encoding with a BOM in front.
+    pub bom: bool,
+}
+
+impl SubtitleWriter for SrtWriter {
+    fn write(&self, document: &SubtitleDocument, out: &mut dyn Write) -> io::Result<()> {
+        let nl = self.line_ending.as_str();
+        if self.bom {
+            out.write_all(UTF8_BOM)?;
+        }
+
+        // numbering has to be 1, 2, 3... in the order the cues are shown
+        let mut cues: Vec<_> = document.cues.iter().collect();
+        cues.sort_by_key(|cue| (cue.start_us, cue.end_us));
+
+        for (idx, cue) in cues.into_iter().enumerate() {
+            if idx > 0 {
+                write!(out, "{}", nl)?;
+            }
+
+            write!(out, "{}{}", idx + 1, nl)?;
+            write!(
+                out,
+                "{} --> {}{}",
+                format_timestamp_microsec(cue.start_us),
+                format_timestamp_microsec(cue.end_us),
+                nl
+            )?;
+            for line in &cue.lines {
+                write!(out, "{}{}", format_line(line), nl)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn format_line(line: &CueLine) -> String {
+    line.spans
+        .iter()
+        .map(|span| {
+            if span.italic {
+                format!("<i>{}</i>", span.text)
+            } else {
+                span.text.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Microseconds as HH:MM:SS,mmm.
+pub fn format_timestamp_microsec(us: u64) -> String {
+    let (hours, minutes, seconds, milliseconds) = split_timestamp(us);
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        hours, minutes, seconds, milliseconds
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subtitle::fixtures::document;
+
+    fn render(writer: &SrtWriter) -> Vec<u8> {
+        let mut out = Vec::new();
+        writer.write(&document(), &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_timestamps_are_zero_padded() {
+        assert_eq!(format_timestamp_microsec(0), "00:00:00,000");
+        assert_eq!(format_timestamp_microsec(62_005_000), "00:01:02,005");
+        assert_eq!(format_timestamp_microsec(3_723_004_999), "01:02:03,004");
+        assert_eq!(format_timestamp_microsec(360_000_000_000), "100:00:00,000");
+    }
+
+    #[test]
+    fn test_golden_lf() {
+        assert_eq!(
+            String::from_utf8(render(&SrtWriter::default())).unwrap(),
+            include_str!("../../tests/golden/basic.srt")
+        );
+    }
+
+    #[test]
+    fn test_golden_crlf_bom() {
+        let writer = SrtWriter {
+            line_ending: LineEnding::CrLf,
+            bom: true,
+        };
+        assert_eq!(
+            render(&writer),
+            &include_bytes!("../../tests/golden/basic_crlf_bom.srt")[..]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::io::{self, Write};
+
+use crate::subtitle::{split_timestamp, Cue, CueLine, Position, SubtitleDocument, SubtitleWriter};
+
+// ticks per second when timing is written in ticks, fine enough for the 90kHz PGS clock
+const TICK_RATE: u64 = 10_000_000;
+
+/// How cue times are written.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum TtmlTiming {
+    /// Clock times, HH:MM:SS.mmm.
+    #[default]
+    Clock,
+    /// Whole ticks at `TICK_RATE`, for delivery specs that ask for them.
+    Ticks,
+}
+
+/// IMSC1 text profile.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TtmlWriter {
+    pub timing: TtmlTiming,
+    pub language: String,
+}
+
+impl Default for TtmlWriter {
+    fn default() -> Self {
+        TtmlWriter {
+            timing: TtmlTiming::default(),
+            language: "en".to_string(),
+        }
+    }
+}
+
+impl TtmlWriter {
+    fn format_time(&self, us: u64) -> String {
+        match self.timing {
+            TtmlTiming::Clock => {
+                let (hours, minutes, seconds, milliseconds) = split_timestamp(us);
+                format!(
+                    "{:02}:{:02}:{:02}.{:03}",
+                    hours, minutes, seconds, milliseconds
+                )
+            }
+            TtmlTiming::Ticks => format!("{}t", us * (TICK_RATE / 1_000_000)),
+        }
+    }
+}
+
+impl SubtitleWriter for TtmlWriter {
+    fn write(&self, document: &SubtitleDocument, out: &mut dyn Write) -> io::Result<()> {
+        let mut cues: Vec<_> = document.cues.iter().collect();
+        cues.sort_by_key(|cue| (cue.start_us, cue.end_us));
+
+        // one region per distinct area the cues' PGS windows cover, cues without one get a region
+        // around the bitmap
+        let mut regions: Vec<Position> = Vec::new();
+        for cue in &cues {
+            let area = cue_area(cue);
+            if !regions.contains(&area) {
+                regions.push(area);
+            }
+        }
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        write!(
+            out,
+            r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" xmlns:tts="http://www.w3.org/ns/ttml#styling" ttp:profile="http://www.w3.org/ns/ttml/profile/imsc1/text" ttp:timeBase="media""#
+        )?;
+        if self.timing == TtmlTiming::Ticks {
+            write!(out, r#" ttp:tickRate="{}""#, TICK_RATE)?;
+        }
+        writeln!(
+            out,
+            r#" tts:extent="{}px {}px" xml:lang="{}">"#,
+            document.width,
+            document.height,
+            escape(&self.language)
+        )?;
+
+        writeln!(out, "  <head>")?;
+        writeln!(out, "    <styling>")?;
+        writeln!(
+            out,
+            r#"      <style xml:id="s0" tts:color="white" tts:fontSize="100%" tts:textAlign="center" tts:textOutline="black 5%"/>"#
+        )?;
+        writeln!(out, "    </styling>")?;
+        writeln!(out, "    <layout>")?;
+        for (idx, region) in regions.iter().enumerate() {
+            writeln!(
+                out,
+                r#"      <region xml:id="r{}" tts:origin="{} {}" tts:extent="{} {}" tts:displayAlign="{}"/>"#,
+                idx,
+                percent(region.x, document.width),
+                percent(region.y, document.height),
+                percent(region.width, document.width),
+                percent(region.height, document.height),
+                display_align(document, region)
+            )?;
+        }
+        writeln!(out, "    </layout>")?;
+        writeln!(out, "  </head>")?;
+
+        writeln!(out, r#"  <body style="s0">"#)?;
+        writeln!(out, "    <div>")?;
+        for cue in cues {
+            let region = regions.iter().position(|r| *r == cue_area(cue)).unwrap();
+            writeln!(
+                out,
+                r#"      <p begin="{}" end="{}" region="r{}">{}</p>"#,
+                self.format_time(cue.start_us),
+                self.format_time(cue.end_us),
+                region,
+                cue.lines
+                    .iter()
+                    .map(format_line)
+                    .collect::<Vec<String>>()
+                    .join("<br/>")
+            )?;
+        }
+        writeln!(out, "    </div>")?;
+        writeln!(out, "  </body>")?;
+        writeln!(out, "</tt>")
+    }
+}
+
+// a cue drawn in two windows (two lines far apart) gets a region holding both
+fn cue_area(cue: &Cue) -> Position {
+    match cue.windows.split_first() {
+        Some((first, rest)) => rest.iter().fold(*first, |area, window| area.union(window)),
+        None => cue.position,
+    }
+}
+
+// text sits at the bottom of regions in the lower half of the frame and at the top otherwise
+fn display_align(document: &SubtitleDocument, region: &Position) -> &'static str {
+    if region.y + region.height / 2 > document.height / 2 {
+        "after"
+    } else {
+        "before"
+    }
+}
+
+fn percent(v: u32, of: u32) -> String {
+    if of == 0 {
+        return "0%".to_string();
+    }
+
+    format!("{:.2}%", 100.0 * v as f64 / of as f64)
+}
+
+fn format_line(line: &CueLine) -> String {
+    line.spans
+        .iter()
+        .map(|span| {
+            let text = escape(&span.text);
+            if span.italic {
+                format!(r#"<span tts:fontStyle="italic">{}</span>"#, text)
+            } else {
+                text
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Text with the XML special characters escaped.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subtitle::fixtures::positioned_document;
+
+    #[test]
+    fn test_golden() {
+        let mut out = Vec::new();
+        TtmlWriter::default()
+            .write(&positioned_document(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            include_str!("../../tests/golden/basic.ttml")
+        );
+    }
+
+    #[test]
+    fn test_cue_area() {
+        let mut cue = positioned_document().cues.remove(0);
+        cue.windows.push(Position {
+            x: 192,
+            y: 864,
+            width: 1536,
+            height: 192,
+        });
+        assert_eq!(
+            cue_area(&cue),
+            Position {
+                x: 192,
+                y: 40,
+                width: 1536,
+                height: 1016,
+            }
+        );
+
+        cue.windows.clear();
+        assert_eq!(cue_area(&cue), cue.position);
+    }
+}
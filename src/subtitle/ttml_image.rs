@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use image::ImageError;
+
+use crate::parser::renderer::Screen;
+use crate::subtitle::split_timestamp;
+use crate::subtitle::ttml::escape;
+
+/// IMSC1 image profile, the rendered bitmaps are written as PNGs next to the document and shown
+/// through `smpte:backgroundImage`, nothing is OCRed. Screens showing the same bitmap share a PNG.
+pub fn write_imsc1_images(screens: &[Screen], path: &Path) -> Result<(), ImageError> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("subs");
+
+    let mut screens: Vec<&Screen> = screens.iter().collect();
+    screens.sort_by_key(|screen| (screen.begin_us, screen.dur_us));
+
+    let mut names: HashMap<u64, String> = HashMap::new();
+    let mut images: Vec<String> = Vec::with_capacity(screens.len());
+    for screen in &screens {
+        if !names.contains_key(&screen.hash) {
+            let name = format!("{}-{:04}.png", stem, names.len() + 1);
+            screen.image.save(dir.join(&name))?;
+            names.insert(screen.hash, name);
+        }
+        images.push(names[&screen.hash].clone());
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    write_document(&mut out, &screens, &images)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+// images[i] is the file name of the PNG for screens[i]
+fn write_document(out: &mut dyn Write, screens: &[&Screen], images: &[String]) -> io::Result<()> {
+    let (width, height) = screens
+        .first()
+        .map(|screen| (screen.video_width, screen.video_height))
+        .unwrap_or((1920, 1080));
+
+    // one region per distinct placement of a bitmap
+    let mut regions: Vec<(u32, u32, u32, u32)> = Vec::new();
+    for screen in screens {
+        let area = screen_area(screen);
+        if !regions.contains(&area) {
+            regions.push(area);
+        }
+    }
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:smpte="http://www.smpte-ra.org/schemas/2052-1/2010/smpte-tt" ttp:profile="http://www.w3.org/ns/ttml/profile/imsc1/image" ttp:timeBase="media" tts:extent="{}px {}px">"#,
+        width, height
+    )?;
+
+    writeln!(out, "  <head>")?;
+    writeln!(out, "    <layout>")?;
+    for (idx, (x, y, w, h)) in regions.iter().enumerate() {
+        writeln!(
+            out,
+            r#"      <region xml:id="r{}" tts:origin="{}px {}px" tts:extent="{}px {}px"/>"#,
+            idx, x, y, w, h
+        )?;
+    }
+    writeln!(out, "    </layout>")?;
+    writeln!(out, "  </head>")?;
+
+    writeln!(out, "  <body>")?;
+    for (screen, image) in screens.iter().zip(images) {
+        let region = regions
+            .iter()
+            .position(|r| *r == screen_area(screen))
+            .unwrap();
+        writeln!(
+            out,
+            r#"    <div begin="{}" end="{}" region="r{}" smpte:backgroundImage="{}"/>"#,
+            format_time(screen.begin_us),
+            format_time(screen.begin_us + screen.dur_us),
+            region,
+            escape(image)
+        )?;
+    }
+    writeln!(out, "  </body>")?;
+    writeln!(out, "</tt>")
+}
+
+fn screen_area(screen: &Screen) -> (u32, u32, u32, u32) {
+    (
+        screen.x,
+        screen.y,
+        screen.image.width(),
+        screen.image.height(),
+    )
+}
+
+fn format_time(us: u64) -> String {
+    let (hours, minutes, seconds, milliseconds) = split_timestamp(us);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        hours, minutes, seconds, milliseconds
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn screen(begin_us: u64, dur_us: u64, x: u32, y: u32, hash: u64) -> Screen {
+        Screen {
+            image: RgbaImage::new(640, 96),
+            begin_us,
+            dur_us,
+            x,
+            y,
+            video_width: 1920,
+            video_height: 1080,
+            windows: Vec::new(),
+            hash,
+        }
+    }
+
+    #[test]
+    fn test_golden() {
+        let screens = [
+            screen(1_000_000, 2_500_000, 640, 940, 1),
+            screen(4_000_000, 1_200_000, 640, 40, 2),
+            screen(5_200_000, 800_000, 640, 940, 1),
+        ];
+        let screens: Vec<&Screen> = screens.iter().collect();
+        let images: Vec<String> = ["subs-0001.png", "subs-0002.png", "subs-0001.png"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        let mut out = Vec::new();
+        write_document(&mut out, &screens, &images).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            include_str!("../../tests/golden/basic_image.ttml")
+        );
+    }
+}
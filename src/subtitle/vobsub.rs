@@ -0,0 +1,543 @@
+use std::cmp::{min, Reverse};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+
+use crate::parser::renderer::Screen;
+use crate::parser::types::Timestamp;
+use crate::subtitle::split_timestamp;
+
+// DVD subtitles are MPEG-2 program stream packs of this size
+const PACK_SIZE: usize = 2048;
+const PACK_HEADER_SIZE: usize = 14;
+// start code, length, flags, header length, PTS and substream id
+const PES_HEADER_SIZE_PTS: usize = 15;
+const PES_HEADER_SIZE: usize = 10;
+// a padding packet needs at least its start code and length
+const MIN_PADDING: usize = 6;
+// private stream 1, subpicture stream 0
+const SUBSTREAM_ID: u8 = 0x20;
+
+const PALETTE_SIZE: usize = 16;
+// pixels less opaque than this are left transparent
+const ALPHA_THRESHOLD: u8 = 128;
+// squared RGB distance under which two colors are treated as the same
+const SAME_COLOR: u32 = 3 * 24 * 24;
+
+/// The DVD frame the screens are scaled to.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum TvSystem {
+    /// 720x480.
+    #[default]
+    Ntsc,
+    /// 720x576.
+    Pal,
+}
+
+impl TvSystem {
+    fn size(&self) -> (u32, u32) {
+        match self {
+            TvSystem::Ntsc => (720, 480),
+            TvSystem::Pal => (720, 576),
+        }
+    }
+}
+
+/// DVD subpictures, an .idx and .sub pair.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VobSubWriter {
+    pub system: TvSystem,
+    pub language: String,
+}
+
+impl Default for VobSubWriter {
+    fn default() -> Self {
+        VobSubWriter {
+            system: TvSystem::default(),
+            language: "en".to_string(),
+        }
+    }
+}
+
+// a screen scaled to the DVD frame and reduced to the four colors a subpicture can show: 0 is
+// the transparent background, 1 to 3 index colors
+#[derive(Debug, PartialEq, Clone)]
+struct Bitmap {
+    begin_us: u64,
+    dur_us: u64,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    colors: Vec<([u8; 3], usize)>,
+}
+
+impl VobSubWriter {
+    /// Writes path with the extensions .idx and .sub.
+    pub fn write(&self, screens: &[Screen], path: &Path) -> io::Result<()> {
+        let mut screens: Vec<&Screen> = screens.iter().collect();
+        screens.sort_by_key(|screen| (screen.begin_us, screen.dur_us));
+
+        let bitmaps: Vec<Bitmap> = screens.iter().map(|screen| self.reduce(screen)).collect();
+        let palette = global_palette(&bitmaps);
+
+        let mut sub: Vec<u8> = Vec::new();
+        let mut index: Vec<(u64, usize)> = Vec::with_capacity(bitmaps.len());
+        for bitmap in &bitmaps {
+            let mut colors = [0u8; 4];
+            let mut alpha = [0u8; 4];
+            for (idx, (color, _)) in bitmap.colors.iter().enumerate() {
+                // entry 0 is the background
+                colors[idx + 1] = 1 + nearest(&palette[1..], color) as u8;
+                alpha[idx + 1] = 0xf;
+            }
+
+            let spu = encode_spu(bitmap, colors, alpha)?;
+            index.push((bitmap.begin_us, sub.len()));
+            write_packs(&mut sub, &spu, Timestamp::from_micros(bitmap.begin_us));
+        }
+
+        File::create(path.with_extension("sub"))?.write_all(&sub)?;
+
+        let mut idx = BufWriter::new(File::create(path.with_extension("idx"))?);
+        self.write_idx(&mut idx, &palette, &index)?;
+        idx.flush()
+    }
+
+    fn write_idx(
+        &self,
+        out: &mut dyn Write,
+        palette: &[[u8; 3]],
+        index: &[(u64, usize)],
+    ) -> io::Result<()> {
+        let (width, height) = self.system.size();
+        writeln!(out, "# VobSub index file, v7 (do not modify this line!)")?;
+        writeln!(out, "size: {}x{}", width, height)?;
+        writeln!(out, "org: 0, 0")?;
+        writeln!(out, "scale: 100%, 100%")?;
+        writeln!(out, "alpha: 100%")?;
+        writeln!(out, "smooth: OFF")?;
+        writeln!(out, "fadein/out: 0, 0")?;
+        writeln!(out, "align: OFF at LEFT TOP")?;
+        writeln!(out, "time offset: 0")?;
+        writeln!(out, "forced subs: OFF")?;
+
+        let mut entries: Vec<String> = palette
+            .iter()
+            .map(|[r, g, b]| format!("{:02x}{:02x}{:02x}", r, g, b))
+            .collect();
+        entries.resize(PALETTE_SIZE, "000000".to_string());
+        writeln!(out, "palette: {}", entries.join(", "))?;
+        writeln!(
+            out,
+            "custom colors: OFF, tridx: 0000, colors: 000000, 000000, 000000, 000000"
+        )?;
+        writeln!(out, "langidx: 0")?;
+        writeln!(out)?;
+        writeln!(out, "id: {}, index: 0", self.language)?;
+        for (begin_us, filepos) in index {
+            let (hours, minutes, seconds, milliseconds) = split_timestamp(*begin_us);
+            writeln!(
+                out,
+                "timestamp: {:02}:{:02}:{:02}:{:03}, filepos: {:09x}",
+                hours, minutes, seconds, milliseconds, filepos
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn reduce(&self, screen: &Screen) -> Bitmap {
+        let (frame_width, frame_height) = self.system.size();
+        let scale_x = frame_width as f64 / screen.video_width.max(1) as f64;
+        let scale_y = frame_height as f64 / screen.video_height.max(1) as f64;
+
+        let x = min((screen.x as f64 * scale_x).round() as u32, frame_width - 1);
+        let y = min((screen.y as f64 * scale_y).round() as u32, frame_height - 1);
+        let width = ((screen.image.width() as f64 * scale_x).round() as u32)
+            .max(1)
+            .min(frame_width - x);
+        let height = ((screen.image.height() as f64 * scale_y).round() as u32)
+            .max(1)
+            .min(frame_height - y);
+
+        let image: RgbaImage = if width == screen.image.width() && height == screen.image.height() {
+            screen.image.clone()
+        } else {
+            imageops::resize(&screen.image, width, height, FilterType::Triangle)
+        };
+
+        // every pixel takes the nearest picked color, no dithering: subtitles are flat fills and
+        // outlines, dithering would only roughen their edges
+        let colors = bitmap_colors(&image);
+        let picked: Vec<[u8; 3]> = colors.iter().map(|(color, _)| *color).collect();
+        let pixels = image
+            .pixels()
+            .map(|p| {
+                if p[3] < ALPHA_THRESHOLD {
+                    0
+                } else {
+                    1 + nearest(&picked, &[p[0], p[1], p[2]]) as u8
+                }
+            })
+            .collect();
+
+        Bitmap {
+            begin_us: screen.begin_us,
+            dur_us: screen.dur_us,
+            x,
+            y,
+            width,
+            height,
+            pixels,
+            colors,
+        }
+    }
+}
+
+// up to three colors for the opaque pixels, most common first, each with its pixel count. Colors
+// are counted at 5 bits per channel so anti-aliasing doesn't split a fill into many shades, and a
+// color close to one already picked is skipped so the outline gets its own slot
+fn bitmap_colors(image: &RgbaImage) -> Vec<([u8; 3], usize)> {
+    let mut buckets: Vec<(u16, [u64; 3], usize)> = Vec::new();
+    let mut slots = vec![usize::MAX; 1 << 15];
+    for p in image.pixels().filter(|p| p[3] >= ALPHA_THRESHOLD) {
+        let key = ((p[0] as usize >> 3) << 10) | ((p[1] as usize >> 3) << 5) | (p[2] as usize >> 3);
+        if slots[key] == usize::MAX {
+            slots[key] = buckets.len();
+            buckets.push((key as u16, [0; 3], 0));
+        }
+        let bucket = &mut buckets[slots[key]];
+        for channel in 0..3 {
+            bucket.1[channel] += p[channel] as u64;
+        }
+        bucket.2 += 1;
+    }
+    buckets.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+
+    let mut picked: Vec<([u8; 3], usize)> = Vec::new();
+    for (_, sums, count) in buckets {
+        let color = [
+            (sums[0] / count as u64) as u8,
+            (sums[1] / count as u64) as u8,
+            (sums[2] / count as u64) as u8,
+        ];
+        match picked
+            .iter()
+            .position(|(c, _)| distance(c, &color) < SAME_COLOR)
+        {
+            Some(idx) => picked[idx].1 += count,
+            None if picked.len() < 3 => picked.push((color, count)),
+            None => {}
+        }
+    }
+
+    picked
+}
+
+// the 16 colors of the .idx, colors used by more pixels win when there are more than that
+fn global_palette(bitmaps: &[Bitmap]) -> Vec<[u8; 3]> {
+    let mut counts: Vec<([u8; 3], usize)> = Vec::new();
+    for (color, count) in bitmaps.iter().flat_map(|bitmap| bitmap.colors.iter()) {
+        match counts
+            .iter_mut()
+            .find(|(c, _)| distance(c, color) < SAME_COLOR)
+        {
+            Some((_, n)) => *n += count,
+            None => counts.push((*color, *count)),
+        }
+    }
+    counts.sort_by_key(|(_, count)| Reverse(*count));
+
+    // entry 0 is left for the transparent background
+    let mut palette = vec![[0, 0, 0]];
+    palette.extend(
+        counts
+            .into_iter()
+            .take(PALETTE_SIZE - 1)
+            .map(|(color, _)| color),
+    );
+    palette
+}
+
+fn distance(a: &[u8; 3], b: &[u8; 3]) -> u32 {
+    (0..3)
+        .map(|i| {
+            let d = a[i] as i32 - b[i] as i32;
+            (d * d) as u32
+        })
+        .sum()
+}
+
+fn nearest(palette: &[[u8; 3]], color: &[u8; 3]) -> usize {
+    (0..palette.len())
+        .min_by_key(|idx| distance(&palette[*idx], color))
+        .unwrap_or(0)
+}
+
+// a subpicture unit: the two interlaced fields, then a control sequence showing it and one
+// hiding it dur_us later
+fn encode_spu(bitmap: &Bitmap, colors: [u8; 4], alpha: [u8; 4]) -> io::Result<Vec<u8>> {
+    let mut top = Vec::new();
+    let mut bottom = Vec::new();
+    for (row, line) in bitmap.pixels.chunks(bitmap.width as usize).enumerate() {
+        if row % 2 == 0 {
+            encode_line(&mut top, line);
+        } else {
+            encode_line(&mut bottom, line);
+        }
+    }
+
+    let top_offset = 4;
+    let bottom_offset = top_offset + top.len();
+    let show_offset = bottom_offset + bottom.len();
+    let hide_offset = show_offset + 24;
+    let size = hide_offset + 6;
+    if size > u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "subpicture too large for a DVD subtitle",
+        ));
+    }
+
+    let x2 = bitmap.x + bitmap.width - 1;
+    let y2 = bitmap.y + bitmap.height - 1;
+    // in units of 1024 90kHz ticks
+    let delay = min(
+        Timestamp::from_micros(bitmap.dur_us).ticks() / 1024,
+        u16::MAX as u64,
+    ) as u16;
+
+    let mut out = Vec::with_capacity(size);
+    out.extend_from_slice(&(size as u16).to_be_bytes());
+    out.extend_from_slice(&(show_offset as u16).to_be_bytes());
+    out.extend_from_slice(&top);
+    out.extend_from_slice(&bottom);
+
+    out.extend_from_slice(&0u16.to_be_bytes());
+    out.extend_from_slice(&(hide_offset as u16).to_be_bytes());
+    out.extend_from_slice(&[0x03, colors[3] << 4 | colors[2], colors[1] << 4 | colors[0]]);
+    out.extend_from_slice(&[0x04, alpha[3] << 4 | alpha[2], alpha[1] << 4 | alpha[0]]);
+    out.extend_from_slice(&[
+        0x05,
+        (bitmap.x >> 4) as u8,
+        ((bitmap.x & 0xf) << 4 | x2 >> 8) as u8,
+        x2 as u8,
+        (bitmap.y >> 4) as u8,
+        ((bitmap.y & 0xf) << 4 | y2 >> 8) as u8,
+        y2 as u8,
+    ]);
+    out.push(0x06);
+    out.extend_from_slice(&(top_offset as u16).to_be_bytes());
+    out.extend_from_slice(&(bottom_offset as u16).to_be_bytes());
+    out.extend_from_slice(&[0x01, 0xff]);
+
+    out.extend_from_slice(&delay.to_be_bytes());
+    out.extend_from_slice(&(hide_offset as u16).to_be_bytes());
+    out.extend_from_slice(&[0x02, 0xff]);
+
+    Ok(out)
+}
+
+// runs of 2 bit pixels as 1 to 4 nibble codes (count << 2 | color), a count of 0 fills the rest
+// of the line. Every line starts on a byte
+fn encode_line(out: &mut Vec<u8>, line: &[u8]) {
+    let mut nibbles: Vec<u8> = Vec::new();
+    let mut idx = 0;
+    while idx < line.len() {
+        let color = line[idx];
+        let mut count = 1;
+        while idx + count < line.len() && line[idx + count] == color && count < 255 {
+            count += 1;
+        }
+
+        let to_end = idx + count == line.len();
+        let code = if to_end && count >= 64 {
+            color as u16
+        } else {
+            (count as u16) << 2 | color as u16
+        };
+        // the leading zero nibbles tell the decoder how long the code is
+        let len = match count {
+            _ if to_end && count >= 64 => 4,
+            1..=3 => 1,
+            4..=15 => 2,
+            16..=63 => 3,
+            _ => 4,
+        };
+        for shift in (0..len).rev() {
+            nibbles.push(((code >> (shift * 4)) & 0xf) as u8);
+        }
+
+        idx += count;
+    }
+
+    for pair in nibbles.chunks(2) {
+        out.push(pair[0] << 4 | pair.get(1).copied().unwrap_or(0));
+    }
+}
+
+// the SPU split over as many packs as it needs, the first one carrying the PTS
+fn write_packs(out: &mut Vec<u8>, spu: &[u8], pts: Timestamp) {
+    let pts = pts.ticks();
+    let mut rest = spu;
+    let mut first = true;
+    while !rest.is_empty() {
+        let header_size = if first {
+            PES_HEADER_SIZE_PTS
+        } else {
+            PES_HEADER_SIZE
+        };
+        let room = PACK_SIZE - PACK_HEADER_SIZE - header_size;
+        let (chunk, remains) = rest.split_at(min(room, rest.len()));
+
+        // the last pack is filled with a padding packet, or with stuffing in the PES header
+        // when there isn't room for one
+        let free = room - chunk.len();
+        let stuffing = if free < MIN_PADDING { free } else { 0 };
+        let padding = free - stuffing;
+
+        write_pack_header(out, pts);
+        let pts_size = if first { 5 } else { 0 };
+        out.extend_from_slice(&[0x00, 0x00, 0x01, 0xbd]);
+        out.extend_from_slice(&((3 + pts_size + stuffing + 1 + chunk.len()) as u16).to_be_bytes());
+        out.push(0x81);
+        out.push(if first { 0x80 } else { 0x00 });
+        out.push((pts_size + stuffing) as u8);
+        if first {
+            out.extend_from_slice(&[
+                0x21 | ((pts >> 29) & 0x0e) as u8,
+                (pts >> 22) as u8,
+                ((pts >> 14) & 0xfe) as u8 | 1,
+                (pts >> 7) as u8,
+                ((pts << 1) & 0xfe) as u8 | 1,
+            ]);
+        }
+        out.resize(out.len() + stuffing, 0xff);
+        out.push(SUBSTREAM_ID);
+        out.extend_from_slice(chunk);
+
+        if padding > 0 {
+            out.extend_from_slice(&[0x00, 0x00, 0x01, 0xbe]);
+            out.extend_from_slice(&((padding - MIN_PADDING) as u16).to_be_bytes());
+            out.resize(out.len() + padding - MIN_PADDING, 0xff);
+        }
+
+        rest = remains;
+        first = false;
+    }
+}
+
+fn write_pack_header(out: &mut Vec<u8>, scr: u64) {
+    out.extend_from_slice(&[
+        0x00,
+        0x00,
+        0x01,
+        0xba,
+        0x44 | ((scr >> 27) & 0x38) as u8 | ((scr >> 28) & 0x03) as u8,
+        (scr >> 20) as u8,
+        ((scr >> 12) & 0xf8) as u8 | 0x04 | ((scr >> 13) & 0x03) as u8,
+        (scr >> 5) as u8,
+        ((scr << 3) & 0xf8) as u8 | 0x04,
+        0x01,
+        // mux rate
+        0x01,
+        0x89,
+        0xc3,
+        // no stuffing
+        0xf8,
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_line() {
+        let mut out = Vec::new();
+        encode_line(&mut out, &[1, 1, 1, 2, 0, 0, 0, 0, 0]);
+        // 3 x 1, 1 x 2, 5 x 0
+        assert_eq!(out, vec![0xd6, 0x14]);
+
+        let mut out = Vec::new();
+        encode_line(&mut out, &[1, 2, 2, 2, 2]);
+        // 1 x 1, 4 x 2 then the padding nibble
+        assert_eq!(out, vec![0x51, 0x20]);
+
+        let mut out = Vec::new();
+        encode_line(&mut out, &[3; 100]);
+        // the rest of the line in color 3
+        assert_eq!(out, vec![0x00, 0x03]);
+
+        // runs that don't reach the end of the line, each followed by 1 x 1
+        for (count, expected) in [
+            (16, vec![0x04, 0x05]),
+            (63, vec![0x0f, 0xc5]),
+            (64, vec![0x01, 0x00, 0x50]),
+            (255, vec![0x03, 0xfc, 0x50]),
+        ] {
+            let mut line = vec![0; count];
+            line.push(1);
+            let mut out = Vec::new();
+            encode_line(&mut out, &line);
+            assert_eq!(out, expected, "run of {}", count);
+        }
+    }
+
+    // reads a line back the way a decoder does, the width filling a count of 0
+    fn decode_line(data: &[u8], width: usize) -> Vec<u8> {
+        let mut nibbles = data.iter().flat_map(|byte| [byte >> 4, byte & 0xf]);
+        let mut line = Vec::new();
+        while line.len() < width {
+            // a code is as long as it takes to reach the least it can be at that length
+            let mut code = 0u16;
+            for least in [0x4, 0x10, 0x40, 0] {
+                code = code << 4 | nibbles.next().unwrap() as u16;
+                if code >= least {
+                    break;
+                }
+            }
+            let count = match code >> 2 {
+                0 => width - line.len(),
+                count => count as usize,
+            };
+            line.resize(line.len() + count, (code & 0x3) as u8);
+        }
+        line
+    }
+
+    #[test]
+    fn test_encode_line_round_trip() {
+        let mut line = Vec::new();
+        for (count, color) in [
+            (20, 0),
+            (1, 1),
+            (63, 2),
+            (64, 3),
+            (255, 0),
+            (300, 1),
+            (3, 2),
+        ] {
+            line.resize(line.len() + count, color);
+        }
+        line.extend_from_slice(&[3; 70]);
+
+        let mut out = Vec::new();
+        encode_line(&mut out, &line);
+        assert_eq!(decode_line(&out, line.len()), line);
+    }
+
+    #[test]
+    fn test_packs_fill_2048_bytes() {
+        for len in [1, 100, 2018, 2019, 2020, 2030, 5000] {
+            let mut out = Vec::new();
+            write_packs(&mut out, &vec![0x55; len], Timestamp::from_ticks(90_000));
+            assert_eq!(out.len() % PACK_SIZE, 0, "spu of {} bytes", len);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::{self, Write};
+
+use crate::subtitle::{split_timestamp, Cue, CueLine, SubtitleDocument, SubtitleWriter};
+
+/// WebVTT, cues placed where the bitmaps were.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct VttWriter;
+
+impl SubtitleWriter for VttWriter {
+    fn write(&self, document: &SubtitleDocument, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "WEBVTT")?;
+
+        let mut cues: Vec<_> = document.cues.iter().collect();
+        cues.sort_by_key(|cue| (cue.start_us, cue.end_us));
+
+        for cue in cues {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{} --> {} {}",
+                format_timestamp(cue.start_us),
+                format_timestamp(cue.end_us),
+                cue_settings(document, cue)
+            )?;
+            for line in &cue.lines {
+                writeln!(out, "{}", format_line(line))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// places the cue where the bitmap was: centered on it horizontally, and pinned by its bottom
+// edge in the lower half of the frame or by its top edge in the upper half
+fn cue_settings(document: &SubtitleDocument, cue: &Cue) -> String {
+    if document.width == 0 || document.height == 0 {
+        return String::new();
+    }
+
+    let pos = &cue.position;
+    let percent = |v: f64, of: u32| 100.0 * v / of as f64;
+    let center_x = percent(pos.x as f64 + pos.width as f64 / 2.0, document.width);
+    let center_y = pos.y as f64 + pos.height as f64 / 2.0;
+
+    let line = if center_y > document.height as f64 / 2.0 {
+        format!(
+            "{:.2}%,end",
+            percent((pos.y + pos.height) as f64, document.height)
+        )
+    } else {
+        format!("{:.2}%,start", percent(pos.y as f64, document.height))
+    };
+
+    format!("line:{} position:{:.2}% align:center", line, center_x)
+}
+
+fn format_line(line: &CueLine) -> String {
+    line.spans
+        .iter()
+        .map(|span| {
+            let text = escape(&span.text);
+            if span.italic {
+                format!("<i>{}</i>", text)
+            } else {
+                text
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Microseconds as HH:MM:SS.mmm.
+pub fn format_timestamp(us: u64) -> String {
+    let (hours, minutes, seconds, milliseconds) = split_timestamp(us);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        hours, minutes, seconds, milliseconds
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subtitle::fixtures::positioned_document;
+
+    #[test]
+    fn test_golden() {
+        let mut out = Vec::new();
+        VttWriter.write(&positioned_document(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            include_str!("../../tests/golden/basic.vtt")
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use cap_parser::parser::decoder_model::check_decoder_model;
+use cap_parser::parser::validate::{validate, Violation};
+
+use crate::{create_output, read_input, Failure};
+
+pub struct ValidateOptions {
+    // cli::STDIO for stdin
+    pub input: PathBuf,
+    // cli::STDIO for stdout
+    pub output: PathBuf,
+    // the decoder model timing and buffer checks as well
+    pub decoder_model: bool,
+}
+
+// the report lists every violation, the stream only fails as a whole once it's written
+pub fn run(options: &ValidateOptions) -> Result<(), Failure> {
+    let buffer = read_input(&options.input)?;
+    let mut violations = validate(&buffer);
+    if options.decoder_model {
+        violations.extend(check_decoder_model(&buffer));
+        violations.sort_by_key(|violation| violation.offset);
+    }
+
+    let mut out = create_output(&options.output)?;
+    write_report(&mut out, &violations)?;
+    out.flush()?;
+
+    match violations.len() {
+        0 => Ok(()),
+        count => Err(Failure::Invalid(count)),
+    }
+}
+
+// one line per violation with the byte offset of the segment breaking the rule
+fn write_report(out: &mut dyn Write, violations: &[Violation]) -> io::Result<()> {
+    for violation in violations {
+        writeln!(out, "{:#010x}  {}", violation.offset, violation.rule)?;
+    }
+
+    match violations.len() {
+        0 => writeln!(out, "no violations"),
+        1 => writeln!(out, "1 violation"),
+        count => writeln!(out, "{} violations", count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cap_parser::parser::validate::Rule;
+
+    #[test]
+    fn test_report() {
+        let violations = [
+            Violation {
+                offset: 0,
+                rule: Rule::MissingEnd,
+            },
+            Violation {
+                offset: 0x1f4,
+                rule: Rule::UndefinedPalette { id: 3 },
+            },
+        ];
+
+        let mut out = Vec::new();
+        write_report(&mut out, &violations).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0x00000000  display set without an END segment\n\
+             0x000001f4  palette 3 isn't defined\n\
+             2 violations\n"
+        );
+    }
+}